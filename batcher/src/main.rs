@@ -11,7 +11,5 @@ pub fn main() {
     Program::run(&inputs, sp1_zkvm::lib::verify::verify_sp1_proof);
 
     // Commit to the public inputs.
-    sp1_zkvm::io::commit_slice(&inputs.old_root);
-    sp1_zkvm::io::commit_slice(&inputs.new_root);
-    sp1_zkvm::io::commit_slice(&inputs.new_tx_hash);
+    sp1_zkvm::io::commit_slice(&inputs.public_values());
 }
@@ -1,7 +1,10 @@
 #![no_main]
 sp1_zkvm::entrypoint!(main);
 
-use lib::ecdsa_record::{inputs::Inputs, program::Program};
+use lib::{
+    ecdsa_record::{inputs::Inputs, program::Program},
+    DELEGATION_APPROVAL,
+};
 
 pub fn main() {
     // Parse the program inputs.
@@ -14,4 +17,10 @@ pub fn main() {
     sp1_zkvm::io::commit_slice(&inputs.keyspace_id);
     sp1_zkvm::io::commit_slice(&inputs.current_key);
     sp1_zkvm::io::commit_slice(&inputs.new_key);
+    if let Some(time_bounds) = &inputs.time_bounds {
+        sp1_zkvm::io::commit_slice(&time_bounds.to_bytes());
+    }
+    if inputs.delegation_approval {
+        sp1_zkvm::io::commit_slice(&DELEGATION_APPROVAL);
+    }
 }
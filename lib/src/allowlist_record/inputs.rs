@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ecdsa_record::k_signature::KSignature, Hash};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the Merkle root of the authorized public keys, which is the record storage.
+    pub allowlist_root: Hash,
+    /// Private input: the uncompressed (64 bytes, without prefix) public key of the signer.
+    pub pub_key: Vec<u8>,
+    /// Private input: the index of the signer leaf in the allowlist.
+    pub index: u32,
+    /// Private input: the Merkle path of the signer leaf, from the leaf level up to the root.
+    pub path: Vec<Hash>,
+    /// Private input: the signature over keccack(keyspace_id, new_key).
+    pub sig: KSignature,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
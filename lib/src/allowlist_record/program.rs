@@ -0,0 +1,144 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    keyspace_key_from_storage,
+    merkle::{compute_root, leaf_hash},
+};
+
+use super::inputs::Inputs;
+
+pub struct Program;
+
+impl Program {
+    pub fn run(inputs: &Inputs) {
+        // Ensure the provided allowlist is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.allowlist_root);
+        assert_eq!(
+            inputs.current_key, current_key,
+            "allowlist_root does not match with current_key"
+        );
+
+        // Compute the `msg_hash`: keccack(keyspace_id, new_key).
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(&inputs.keyspace_id);
+        k.update(&inputs.new_key);
+        k.finalize(&mut msg_hash);
+
+        // Ensure the signature is from the provided public key.
+        let recovered_pub_key = inputs
+            .sig
+            .ecrecover(&msg_hash)
+            .expect("failed to recover the public key");
+        assert_eq!(
+            recovered_pub_key.as_slice(),
+            inputs.pub_key,
+            "signer is not the provided public key"
+        );
+
+        // Ensure the public key belongs to the allowlist.
+        let root = compute_root(&leaf_hash(&inputs.pub_key), inputs.index, &inputs.path);
+        assert_eq!(
+            root,
+            Some(inputs.allowlist_root),
+            "public key is not in the allowlist"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        merkle::node_hash,
+        test_utils::{pub_key, sign, signing_key},
+        Hash,
+    };
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+
+    /// Builds the allowlist of the keys with seeds 1 to 5, padded with zero leaves, and returns its
+    /// root and the path of the leaf at `index`.
+    fn allowlist(index: u32) -> (Hash, Vec<Hash>) {
+        let mut layer = (1..=5)
+            .map(|seed| leaf_hash(&pub_key(&signing_key(seed))))
+            .collect::<Vec<_>>();
+        layer.resize(8, [0; 32]);
+
+        let mut path = vec![];
+        let mut index = index as usize;
+        while layer.len() > 1 {
+            path.push(layer[index ^ 1]);
+            layer = layer
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], &pair[1]))
+                .collect();
+            index /= 2;
+        }
+
+        (layer[0], path)
+    }
+
+    /// Returns the inputs for the key with `seed` to update the record, claiming the allowlist `index`.
+    fn inputs(seed: u8, index: u32) -> Inputs {
+        let (allowlist_root, path) = allowlist(index);
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &allowlist_root);
+        let new_key = [42; 32];
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            allowlist_root,
+            pub_key: pub_key(&signing_key(seed)).to_vec(),
+            index,
+            path,
+            sig: sign(&signing_key(seed), &keyspace_id, &new_key),
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        for index in 0..5 {
+            Program::run(&inputs(index as u8 + 1, index));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "public key is not in the allowlist")]
+    fn test_run_unknown_key() {
+        Program::run(&inputs(6, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "public key is not in the allowlist")]
+    fn test_run_invalid_index() {
+        Program::run(&inputs(2, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "public key is not in the allowlist")]
+    fn test_run_index_out_of_tree() {
+        let mut inputs = inputs(1, 0);
+        inputs.index = 8;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "signer is not the provided public key")]
+    fn test_run_invalid_signer() {
+        let mut inputs = inputs(1, 0);
+        inputs.sig = sign(&signing_key(2), &inputs.keyspace_id, &inputs.new_key);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "allowlist_root does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(1, 0);
+        inputs.allowlist_root = [1; 32];
+        Program::run(&inputs);
+    }
+}
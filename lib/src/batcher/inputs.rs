@@ -1,8 +1,15 @@
+use anyhow::{ensure, Context, Result};
 use serde::{Deserialize, Serialize};
 
-use crate::Hash;
+use crate::{Hash, DELEGATION_APPROVAL};
 
-use super::tx::Tx;
+use super::tx::{
+    onchain::{OnchainTx, OnchainTxResult},
+    Tx,
+};
+
+/// The length of the batcher program public values preceding the onchain tx results.
+const FIXED_PUBLIC_VALUES_LEN: usize = 32 * 6 + 8 * 2;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Inputs {
@@ -12,7 +19,123 @@ pub struct Inputs {
     pub new_root: Hash,
     /// Public input: the expected new transaction hash.
     pub new_tx_hash: Hash,
+    /// Public input: the batch timestamp, checked against the record proofs time bounds.
+    pub timestamp: u64,
+    /// Public input: the L1 block number the batch is built on.
+    ///
+    /// The batcher program does not interpret it: the L1 contract is expected to check that
+    /// `block_hash` is the hash of this block and that `timestamp` is consistent with it.
+    pub block_number: u64,
+    /// Public input: the L1 block hash the batch is built on.
+    pub block_hash: Hash,
+    /// Public input: the onchain queue hash the batch starts consuming from.
+    ///
+    /// The L1 contract enqueues every update requested onchain into a hash chain (see
+    /// [OnchainTx::next_queue_hash]), and checks that this is the position consumed by the previous
+    /// batch.
+    pub onchain_queue_start: Hash,
+    /// Public input: the expected onchain queue hash after consuming the onchain txs, in order.
+    ///
+    /// The L1 contract checks that it is a position of its queue, and can force the inclusion of
+    /// the requests older than a deadline by rejecting the batches that do not consume them.
+    pub onchain_queue_end: Hash,
+    /// Public input: the expected result of each onchain tx, in order.
+    ///
+    /// It is committed as one reason code per onchain tx (see [OnchainTxResult]), so that the L1
+    /// contract and the users can tell whether the updates requested onchain were applied.
+    pub onchain_results: Vec<OnchainTxResult>,
 
     /// Private input: the list of transactions to process.
     pub txs: Vec<Tx>,
 }
+
+impl Inputs {
+    /// Returns the public values committed by the batcher program:
+    /// old_root || new_root || new_tx_hash || timestamp || block_number || block_hash
+    /// || onchain_queue_start || onchain_queue_end || onchain_results (integers are big endian,
+    /// onchain results are one byte each).
+    pub fn public_values(&self) -> Vec<u8> {
+        let mut public_values =
+            Vec::with_capacity(FIXED_PUBLIC_VALUES_LEN + self.onchain_results.len());
+        public_values.extend(self.old_root);
+        public_values.extend(self.new_root);
+        public_values.extend(self.new_tx_hash);
+        public_values.extend(self.timestamp.to_be_bytes());
+        public_values.extend(self.block_number.to_be_bytes());
+        public_values.extend(self.block_hash);
+        public_values.extend(self.onchain_queue_start);
+        public_values.extend(self.onchain_queue_end);
+        public_values.extend(self.onchain_results.iter().map(|result| result.code()));
+        public_values
+    }
+
+    /// Returns the onchain queue hash after consuming the onchain txs of the batch, in order.
+    pub fn consumed_onchain_queue(&self) -> Hash {
+        self.txs
+            .iter()
+            .fold(self.onchain_queue_start, |queue_hash, tx| match tx {
+                Tx::Onchain(onchain) => onchain.next_queue_hash(&queue_hash),
+                Tx::Offchain(_) => queue_hash,
+            })
+    }
+
+    /// Returns whether a record proof can read the Keyspace from the given `root`.
+    ///
+    /// Only the batch `old_root` is accepted: it is a public input, checked by the L1 contract to be
+    /// the latest proven Keyspace root.
+    pub fn accepts_keyspace_root(&self, root: &Hash) -> bool {
+        *root == self.old_root
+    }
+
+    /// Returns the result of the `onchain` tx in the batch.
+    ///
+    /// Onchain txs are forced by the L1 contract and are skipped, instead of failing the batch,
+    /// if their record proof is invalid or reads the Keyspace from a root that is not accepted.
+    pub fn onchain_tx_result(&self, onchain: &OnchainTx) -> OnchainTxResult {
+        if onchain.proof.keyspace_root == Some(DELEGATION_APPROVAL) {
+            return OnchainTxResult::DelegationApproval;
+        }
+
+        let accepted_root = match &onchain.proof.keyspace_root {
+            Some(root) => self.accepts_keyspace_root(root),
+            None => true,
+        };
+        if !accepted_root {
+            return OnchainTxResult::KeyspaceRootNotAccepted;
+        }
+
+        onchain.record_proof_result(self.timestamp)
+    }
+
+    /// Returns the result of each onchain tx of the batch, in order.
+    ///
+    /// This verifies the onchain record proofs: the batcher program computes them while applying
+    /// the txs instead.
+    pub fn onchain_tx_results(&self) -> Vec<OnchainTxResult> {
+        self.txs
+            .iter()
+            .filter_map(|tx| match tx {
+                Tx::Onchain(onchain) => Some(self.onchain_tx_result(onchain)),
+                Tx::Offchain(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Decodes the onchain tx results committed by the batcher program from its `public_values`
+/// (see [Inputs::public_values]).
+pub fn parse_onchain_results(public_values: &[u8]) -> Result<Vec<OnchainTxResult>> {
+    ensure!(
+        public_values.len() >= FIXED_PUBLIC_VALUES_LEN,
+        "invalid batch public values length: {}",
+        public_values.len()
+    );
+
+    public_values[FIXED_PUBLIC_VALUES_LEN..]
+        .iter()
+        .map(|code| {
+            OnchainTxResult::from_code(*code)
+                .with_context(|| format!("invalid onchain tx result code: {code}"))
+        })
+        .collect()
+}
@@ -1,3 +1,5 @@
+use crate::DELEGATION_APPROVAL;
+
 use super::{inputs::Inputs, proof::sp1::Sp1ProofVerify};
 
 pub struct Program;
@@ -6,28 +8,52 @@ impl Program {
     pub fn run(inputs: &Inputs, sp1_verify: Sp1ProofVerify) {
         let mut root = inputs.old_root;
         let mut tx_hash = [0; 32];
+        let mut onchain_results = Vec::with_capacity(inputs.onchain_results.len());
 
         for tx in &inputs.txs {
             // 1. Chain the tx hashes.
             tx_hash = tx.hash();
 
-            // 2. Verify the record proof.
+            // 2. Verify the IMTMutate and compute the new root.
+            //
+            // The IMTMutate of a skipped onchain tx is verified as well: it binds the current key
+            // its record proof is checked against to the Keyspace state.
+            let new_root = tx
+                .verify_imt_mutate(&root)
+                .expect("failed to verify the IMTMutate");
+
+            // 3. Verify the record proof.
             //
             // The record proof MUST be valid for offchain txs and MAY be invalid for onchain txs.
             // If an onchain tx has an invalid record proof, it is skipped (its IMTMutate is not applied).
+            //
+            // A record proof committing to time bounds that do not contain the batch timestamp is invalid.
+            // A record proof committing to a Keyspace root that is not accepted by the batch is invalid.
+            // A delegation approval is not an update: its purpose word sits where the Keyspace root would be.
             match tx {
-                crate::batcher::tx::Tx::Offchain(offchain) => offchain.process_proof(sp1_verify),
+                crate::batcher::tx::Tx::Offchain(offchain) => {
+                    assert_ne!(
+                        tx.keyspace_root(),
+                        Some(&DELEGATION_APPROVAL),
+                        "record proof is a delegation approval, not an update"
+                    );
+                    let accepted_root = match tx.keyspace_root() {
+                        Some(root) => inputs.accepts_keyspace_root(root),
+                        None => true,
+                    };
+                    assert!(accepted_root, "record proof Keyspace root is not accepted");
+                    offchain.process_proof(inputs.timestamp, sp1_verify)
+                }
                 crate::batcher::tx::Tx::Onchain(onchain) => {
-                    if !onchain.is_valid_record_proof() {
+                    let result = inputs.onchain_tx_result(onchain);
+                    onchain_results.push(result);
+                    if !result.is_applied() {
                         continue;
                     }
                 }
             };
 
-            // 3. Verify the IMTMutate and compute the new root.
-            root = tx
-                .verify_imt_mutate(&root)
-                .expect("failed to verify the IMTMutate");
+            root = new_root;
         }
 
         // Make sure the final root obtained after applying the txs matches with the provided new_root.
@@ -35,5 +61,430 @@ impl Program {
 
         // Make sure the final tx hash obtained after applying the txs matches with the provided new_tx_hash.
         assert_eq!(tx_hash, inputs.new_tx_hash);
+
+        // Make sure the onchain txs are consumed from the onchain queue, in order (skipped txs are
+        // consumed as well).
+        assert_eq!(
+            inputs.consumed_onchain_queue(),
+            inputs.onchain_queue_end,
+            "onchain txs do not match with the onchain queue"
+        );
+
+        // Make sure the result of each onchain tx matches with the provided onchain_results.
+        assert_eq!(
+            onchain_results, inputs.onchain_results,
+            "onchain tx results do not match"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use imt::circuits::imt::Imt;
+    use k256::{ecdsa, schnorr};
+    use tiny_keccak::Keccak;
+
+    use crate::{
+        batcher::{
+            inputs::parse_onchain_results,
+            proof::{plonk::PLONKProof, Proof},
+            tx::{onchain::OnchainTxResult, Tx},
+        },
+        delegate_record,
+        ecdsa_record::{self, k_signature::KSignature},
+        hash_storage, keyspace_key_from_storage, schnorr_record, Hash,
+    };
+
+    use super::*;
+
+    const ECDSA_VK_HASH: Hash = [0xaa; 32];
+    const SCHNORR_VK_HASH: Hash = [0xbb; 32];
+    const DELEGATE_VK_HASH: Hash = [0xcc; 32];
+
+    fn ecdsa_storage(signing_key: &ecdsa::SigningKey) -> Vec<u8> {
+        signing_key
+            .verifying_key()
+            .to_encoded_point(false)
+            .as_bytes()[1..]
+            .to_vec()
+    }
+
+    /// Proves an update, or a delegation approval, with the ECDSA record program.
+    fn prove_ecdsa_update(
+        signing_key: &ecdsa::SigningKey,
+        keyspace_id: Hash,
+        new_key: Hash,
+        delegation_approval: bool,
+    ) {
+        let msg_hash = ecdsa_record::program::Program::msg_hash(
+            &keyspace_id,
+            &new_key,
+            None,
+            delegation_approval,
+        );
+        let (sig, recid) = signing_key.sign_prehash_recoverable(&msg_hash).unwrap();
+
+        ecdsa_record::program::Program::run(&ecdsa_record::inputs::Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            sig: KSignature {
+                sig: sig.to_bytes().into(),
+                recid: recid.to_byte(),
+            },
+            salt: None,
+            time_bounds: None,
+            delegation_approval,
+            vk_hash: ECDSA_VK_HASH,
+        });
+    }
+
+    /// Proves an update with the Schnorr record program.
+    fn prove_schnorr_update(
+        signing_key: &schnorr::SigningKey,
+        keyspace_id: Hash,
+        current_key: Hash,
+        new_key: Hash,
+    ) {
+        let msg = schnorr_record::program::Program::msg(&keyspace_id, &new_key, false);
+
+        schnorr_record::program::Program::run(&schnorr_record::inputs::Inputs {
+            keyspace_id,
+            current_key,
+            new_key,
+            pub_key: signing_key.verifying_key().to_bytes().into(),
+            sig: signing_key
+                .sign_raw(&msg, &[0; 32])
+                .unwrap()
+                .to_bytes()
+                .to_vec(),
+            delegation_approval: false,
+            vk_hash: SCHNORR_VK_HASH,
+        });
+    }
+
+    fn batch(old_root: Hash, new_root: Hash, txs: Vec<Tx>) -> Inputs {
+        Inputs {
+            old_root,
+            new_root,
+            new_tx_hash: txs.last().unwrap().hash(),
+            timestamp: 0,
+            block_number: 0,
+            block_hash: [0; 32],
+            onchain_queue_start: [0; 32],
+            onchain_queue_end: [0; 32],
+            onchain_results: vec![],
+            txs,
+        }
+    }
+
+    #[test]
+    fn test_migrate_ecdsa_to_schnorr() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+        let mut imt = Imt::new(Keccak::v256);
+        let old_root = imt.root;
+
+        let ecdsa_key = ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        let schnorr_key = schnorr::SigningKey::from_bytes(&[2; 32]).unwrap();
+        let ecdsa_storage = ecdsa_storage(&ecdsa_key);
+        let keyspace_id = keyspace_key_from_storage(&ECDSA_VK_HASH, &ecdsa_storage);
+
+        // 1. The ECDSA record approves a new key derived with the Schnorr program vk hash.
+        let schnorr_storage: Hash = schnorr_key.verifying_key().to_bytes().into();
+        let migrated_key = keyspace_key_from_storage(&SCHNORR_VK_HASH, &schnorr_storage);
+        prove_ecdsa_update(&ecdsa_key, keyspace_id, migrated_key, false);
+
+        let migrate_tx = Tx::offchain(
+            imt.insert_node(keyspace_id, migrated_key),
+            [0; 32],
+            Proof::sp1(ECDSA_VK_HASH, hash_storage(&ecdsa_storage), None, None),
+        );
+
+        // 2. The following update is proven with the Schnorr program.
+        let new_key = keyspace_key_from_storage(&SCHNORR_VK_HASH, &[3; 32]);
+        prove_schnorr_update(&schnorr_key, keyspace_id, migrated_key, new_key);
+
+        let update_tx = Tx::offchain(
+            imt.update_node(keyspace_id, new_key),
+            migrate_tx.hash(),
+            Proof::sp1(SCHNORR_VK_HASH, hash_storage(&schnorr_storage), None, None),
+        );
+
+        Program::run(
+            &batch(old_root, imt.root, vec![migrate_tx, update_tx]),
+            sp1_verify,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "record_vk_hash does not match with current_key")]
+    fn test_migrated_id_rejects_old_program() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+        let mut imt = Imt::new(Keccak::v256);
+        let old_root = imt.root;
+
+        let ecdsa_key = ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        let ecdsa_storage = ecdsa_storage(&ecdsa_key);
+        let keyspace_id = keyspace_key_from_storage(&ECDSA_VK_HASH, &ecdsa_storage);
+
+        let migrated_key = keyspace_key_from_storage(&SCHNORR_VK_HASH, &[2; 32]);
+        let migrate_tx = Tx::offchain(
+            imt.insert_node(keyspace_id, migrated_key),
+            [0; 32],
+            Proof::sp1(ECDSA_VK_HASH, hash_storage(&ecdsa_storage), None, None),
+        );
+
+        // Once migrated, the ECDSA program no longer controls the id, even with the same storage.
+        let update_tx = Tx::offchain(
+            imt.update_node(keyspace_id, [42; 32]),
+            migrate_tx.hash(),
+            Proof::sp1(ECDSA_VK_HASH, hash_storage(&ecdsa_storage), None, None),
+        );
+
+        Program::run(
+            &batch(old_root, imt.root, vec![migrate_tx, update_tx]),
+            sp1_verify,
+        );
+    }
+
+    /// Returns a batch updating an id delegated to an ECDSA parent, with a record proof reading the
+    /// parent from `keyspace_root` (`None` for the batch `old_root`).
+    fn delegate_batch(keyspace_root: Option<Hash>) -> Inputs {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+        let mut imt = Imt::new(Keccak::v256);
+
+        let parent_storage = ecdsa_storage(&ecdsa::SigningKey::from_slice(&[1; 32]).unwrap());
+        let parent_id = keyspace_key_from_storage(&ECDSA_VK_HASH, &parent_storage);
+        let keyspace_id = keyspace_key_from_storage(&DELEGATE_VK_HASH, &parent_id);
+
+        // The parent and delegated ids are registered in previous batches.
+        imt.insert_node(parent_id, parent_id);
+        imt.insert_node(keyspace_id, keyspace_id);
+        let old_root = imt.root;
+
+        // The parent current key is read from the batch `old_root`, but the record proof may claim
+        // another Keyspace root.
+        let keyspace_root = keyspace_root.unwrap_or(old_root);
+        let parent_inclusion = imt.update_node(parent_id, parent_id);
+        let new_key = [42; 32];
+        let time_bounds = delegate_record::program::Program::run(
+            &delegate_record::inputs::Inputs {
+                keyspace_id,
+                current_key: keyspace_id,
+                new_key,
+                keyspace_root: old_root,
+                parent_id,
+                parent_inclusion,
+                parent_vk_hash: ECDSA_VK_HASH,
+                parent_storage_hash: hash_storage(&parent_storage),
+                parent_time_bounds: None,
+                vk_hash: DELEGATE_VK_HASH,
+            },
+            sp1_verify,
+        );
+
+        let tx = Tx::offchain(
+            imt.update_node(keyspace_id, new_key),
+            [0; 32],
+            Proof::sp1(
+                DELEGATE_VK_HASH,
+                hash_storage(&parent_id),
+                time_bounds,
+                Some(keyspace_root),
+            ),
+        );
+
+        batch(old_root, imt.root, vec![tx])
+    }
+
+    #[test]
+    fn test_delegate_update_reads_old_root() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        let inputs = delegate_batch(None);
+        assert!(inputs.accepts_keyspace_root(&inputs.old_root));
+
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "record proof Keyspace root is not accepted")]
+    fn test_delegate_update_rejects_other_root() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        Program::run(&delegate_batch(Some([7; 32])), sp1_verify);
+    }
+
+    /// Returns the ECDSA parent id and storage of a delegated id, with the key its parent approves
+    /// to update it to [42; 32].
+    fn delegation_approval() -> (Hash, Vec<u8>, Hash) {
+        let parent_key = ecdsa::SigningKey::from_slice(&[1; 32]).unwrap();
+        let parent_storage = ecdsa_storage(&parent_key);
+        let parent_id = keyspace_key_from_storage(&ECDSA_VK_HASH, &parent_storage);
+        let keyspace_id = keyspace_key_from_storage(&DELEGATE_VK_HASH, &parent_id);
+
+        let delegation_key =
+            delegate_record::program::Program::delegation_key(&keyspace_id, &[42; 32]);
+        prove_ecdsa_update(&parent_key, parent_id, delegation_key, true);
+
+        (parent_id, parent_storage, delegation_key)
+    }
+
+    #[test]
+    #[should_panic(expected = "record proof is a delegation approval, not an update")]
+    fn test_replayed_delegation_approval() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+        let mut imt = Imt::new(Keccak::v256);
+
+        let (parent_id, parent_storage, delegation_key) = delegation_approval();
+        imt.insert_node(parent_id, parent_id);
+        let old_root = imt.root;
+
+        // The parent approval is replayed as an update of the parent to the delegation key.
+        let tx = Tx::offchain(
+            imt.update_node(parent_id, delegation_key),
+            [0; 32],
+            Proof::sp1(
+                ECDSA_VK_HASH,
+                hash_storage(&parent_storage),
+                None,
+                Some(DELEGATION_APPROVAL),
+            ),
+        );
+
+        Program::run(&batch(old_root, imt.root, vec![tx]), sp1_verify);
+    }
+
+    #[test]
+    fn test_replayed_onchain_delegation_approval() {
+        let mut imt = Imt::new(Keccak::v256);
+
+        let (parent_id, parent_storage, delegation_key) = delegation_approval();
+        imt.insert_node(parent_id, parent_id);
+        let old_root = imt.root;
+
+        let tx = Tx::onchain(
+            imt.update_node(parent_id, delegation_key),
+            [0; 32],
+            PLONKProof {
+                vk: vec![0xaa; 4],
+                proof: vec![0xbb; 40],
+                plonk_vk_hash: "0".to_string(),
+                zkvm_vk_hash: "0".to_string(),
+                storage_hash: hash_storage(&parent_storage),
+                time_bounds: None,
+                keyspace_root: Some(DELEGATION_APPROVAL),
+            },
+        );
+
+        // The approval is skipped instead of updating the parent to the delegation key.
+        let inputs = batch(old_root, old_root, vec![tx]);
+        let Tx::Onchain(onchain) = &inputs.txs[0] else {
+            unreachable!();
+        };
+        assert_eq!(
+            inputs.onchain_tx_result(onchain),
+            OnchainTxResult::DelegationApproval
+        );
+    }
+
+    /// Returns an onchain tx whose record proof does not control `keyspace_id`, which the batcher
+    /// program skips.
+    fn skipped_onchain_tx(keyspace_id: Hash, prev_tx_hash: Hash) -> Tx {
+        Tx::onchain(
+            Imt::new(Keccak::v256).insert_node(keyspace_id, [42; 32]),
+            prev_tx_hash,
+            PLONKProof {
+                vk: vec![0xaa; 4],
+                proof: vec![0xbb; 40],
+                plonk_vk_hash: "0".to_string(),
+                zkvm_vk_hash: "0".to_string(),
+                storage_hash: [0; 32],
+                time_bounds: None,
+                keyspace_root: None,
+            },
+        )
+    }
+
+    /// Returns a batch consuming two onchain txs from the queue, with the queue end computed by
+    /// consuming them in the `consumed` order.
+    fn onchain_batch(consumed: [usize; 2]) -> Inputs {
+        let root = Imt::new(Keccak::v256).root;
+        let first = skipped_onchain_tx([1; 32], [0; 32]);
+        let second = skipped_onchain_tx([2; 32], first.hash());
+
+        let mut inputs = batch(root, root, vec![first, second]);
+        inputs.onchain_queue_start = [7; 32];
+        inputs.onchain_queue_end = consumed.iter().fold([7; 32], |queue_hash, index| {
+            let Tx::Onchain(onchain) = &inputs.txs[*index] else {
+                unreachable!();
+            };
+            onchain.next_queue_hash(&queue_hash)
+        });
+        inputs.onchain_results = vec![OnchainTxResult::NotControlled; 2];
+
+        inputs
+    }
+
+    #[test]
+    fn test_consume_onchain_queue() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        let inputs = onchain_batch([0, 1]);
+        assert_ne!(inputs.onchain_queue_end, inputs.onchain_queue_start);
+
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "onchain txs do not match with the onchain queue")]
+    fn test_onchain_queue_order() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        Program::run(&onchain_batch([1, 0]), sp1_verify);
+    }
+
+    #[test]
+    fn test_commit_onchain_results() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        let inputs = onchain_batch([0, 1]);
+        assert_eq!(inputs.onchain_tx_results(), inputs.onchain_results);
+        Program::run(&inputs, sp1_verify);
+
+        assert_eq!(
+            parse_onchain_results(&inputs.public_values()).unwrap(),
+            [OnchainTxResult::NotControlled; 2]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "onchain tx results do not match")]
+    fn test_onchain_results_mismatch() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        let mut inputs = onchain_batch([0, 1]);
+        inputs.onchain_results[1] = OnchainTxResult::Applied;
+
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to verify the IMTMutate")]
+    fn test_skipped_onchain_tx_mutate_is_verified() {
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+
+        // The id is already registered: a skipped tx cannot claim its current key is the id
+        // itself with an insertion.
+        let mut imt = Imt::new(Keccak::v256);
+        imt.insert_node([1; 32], [10; 32]);
+        let mut inputs = batch(
+            imt.root,
+            imt.root,
+            vec![skipped_onchain_tx([1; 32], [0; 32])],
+        );
+        inputs.onchain_results = vec![OnchainTxResult::NotControlled];
+
+        Program::run(&inputs, sp1_verify);
     }
 }
@@ -2,6 +2,8 @@ use plonk::PLONKProof;
 use serde::{Deserialize, Serialize};
 use sp1::SP1Proof;
 
+use crate::{keyspace_key_from_storage_hash, time_bounds::TimeBounds, Hash};
+
 pub mod plonk;
 pub mod sp1;
 
@@ -12,10 +14,17 @@ pub enum Proof {
 }
 
 impl Proof {
-    pub fn sp1(record_vk_hash: [u8; 32], storage_hash: [u8; 32]) -> Self {
+    pub fn sp1(
+        record_vk_hash: [u8; 32],
+        storage_hash: [u8; 32],
+        time_bounds: Option<TimeBounds>,
+        keyspace_root: Option<Hash>,
+    ) -> Self {
         Self::SP1(SP1Proof {
             record_vk_hash,
             storage_hash,
+            time_bounds,
+            keyspace_root,
         })
     }
 
@@ -25,6 +34,8 @@ impl Proof {
         plonk_vk_hash: String,
         zkvm_vk_hash: String,
         storage_hash: [u8; 32],
+        time_bounds: Option<TimeBounds>,
+        keyspace_root: Option<Hash>,
     ) -> Self {
         Self::PLONK(PLONKProof {
             vk: vk.into(),
@@ -32,6 +43,45 @@ impl Proof {
             plonk_vk_hash,
             zkvm_vk_hash,
             storage_hash,
+            time_bounds,
+            keyspace_root,
         })
     }
+
+    pub fn time_bounds(&self) -> Option<&TimeBounds> {
+        match self {
+            Proof::SP1(proof) => proof.time_bounds.as_ref(),
+            Proof::PLONK(proof) => proof.time_bounds.as_ref(),
+        }
+    }
+
+    pub fn keyspace_root(&self) -> Option<&Hash> {
+        match self {
+            Proof::SP1(proof) => proof.keyspace_root.as_ref(),
+            Proof::PLONK(proof) => proof.keyspace_root.as_ref(),
+        }
+    }
+
+    /// Returns the record verifier key hash the proof commits to, or `None` if a PLONK proof
+    /// carries an invalid one.
+    pub fn record_vk_hash(&self) -> Option<Hash> {
+        match self {
+            Proof::SP1(proof) => Some(proof.record_vk_hash),
+            Proof::PLONK(proof) => proof.record_vk_hash(),
+        }
+    }
+
+    /// Returns whether the record behind the proof controls `current_key`, i.e. whether its
+    /// verifier key hash and storage hash hash to it.
+    ///
+    /// This is the check the batcher program enforces on every offchain tx.
+    pub fn controls(&self, current_key: &Hash) -> bool {
+        match self {
+            Proof::SP1(proof) => {
+                keyspace_key_from_storage_hash(&proof.record_vk_hash, &proof.storage_hash)
+                    == *current_key
+            }
+            Proof::PLONK(proof) => proof.controls(current_key),
+        }
+    }
 }
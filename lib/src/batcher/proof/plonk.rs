@@ -5,7 +5,7 @@ use num_traits::Num;
 use serde::{Deserialize, Serialize};
 use sp1_core::io::SP1PublicValues;
 
-use crate::{keyspace_key_from_storage_hash, Hash};
+use crate::{keyspace_key_from_storage_hash, record_public_values, time_bounds::TimeBounds, Hash};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct PLONKProof {
@@ -20,9 +20,26 @@ pub struct PLONKProof {
 
     /// The storage hash.
     pub storage_hash: Hash,
+    /// The time bounds committed by the record proof, if any.
+    pub time_bounds: Option<TimeBounds>,
+    /// The Keyspace root committed by the record proof, if any.
+    pub keyspace_root: Option<Hash>,
 }
 
 impl PLONKProof {
+    /// Returns the record verifier key hash, decoded from the plonk's verifier key hash.
+    pub fn record_vk_hash(&self) -> Option<Hash> {
+        let vk_hash_num = BigUint::from_str_radix(&self.plonk_vk_hash, 10).ok()?;
+        vk_hash_num.to_bytes_be().as_slice().try_into().ok()
+    }
+
+    /// Returns whether the record behind the proof controls `current_key`.
+    pub fn controls(&self, current_key: &Hash) -> bool {
+        self.record_vk_hash().is_some_and(|vk_hash| {
+            keyspace_key_from_storage_hash(&vk_hash, &self.storage_hash) == *current_key
+        })
+    }
+
     pub fn is_valid_record_proof(&self, imt_mutate: &IMTMutate<Hash, Hash>) -> bool {
         let (keyspace_id, current_key, new_key) = match imt_mutate {
             IMTMutate::Insert(insert) => (insert.node.key, insert.node.key, insert.node.value),
@@ -34,15 +51,25 @@ impl PLONKProof {
         // This check is CRITICAL to ensure that the provided `record_vk_hash` is indeed the one
         // that has control over the KeySpace id. Without this check a malicious user could provide
         // an arbitrary `record_vk_hash` and update any KeySpace record.
-        let vk_hash_num = BigUint::from_str_radix(&self.plonk_vk_hash, 10).unwrap();
-        let vk_hash = &vk_hash_num.to_bytes_be().as_slice().try_into().unwrap();
-        let keyspace_key = keyspace_key_from_storage_hash(vk_hash, &self.storage_hash);
+        let vk_hash = self.record_vk_hash().expect("invalid plonk_vk_hash");
+        let keyspace_key = keyspace_key_from_storage_hash(&vk_hash, &self.storage_hash);
         assert_eq!(current_key, keyspace_key);
 
-        let mut pub_inputs = [0; 96];
-        pub_inputs[..32].copy_from_slice(&keyspace_id);
-        pub_inputs[32..64].copy_from_slice(&current_key);
-        pub_inputs[64..].copy_from_slice(&new_key);
+        self.verify_update(&keyspace_id, &current_key, &new_key)
+    }
+
+    /// Verifies the PLONK proof of the update of `keyspace_id` from `current_key` to `new_key`.
+    ///
+    /// This does NOT check that the record controls `current_key` (see
+    /// [PLONKProof::is_valid_record_proof]).
+    pub fn verify_update(&self, keyspace_id: &Hash, current_key: &Hash, new_key: &Hash) -> bool {
+        let pub_inputs = record_public_values(
+            keyspace_id,
+            current_key,
+            new_key,
+            self.time_bounds.as_ref(),
+            self.keyspace_root.as_ref(),
+        );
         // There are two potential ways to calculate the public values digest after concatenating the values. The straightforward way is Sha256::digest(), which is what commit_to_proof does in lib::batcher::proof::sp1. The other way is to use SP1PublicValues::hash(), which calculates the hash slightly differently. This latter method matches the public inputs digest obtained during serialize_plonk().
         let public_values_digest = SP1PublicValues::from(&pub_inputs).hash();
 
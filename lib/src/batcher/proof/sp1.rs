@@ -2,7 +2,7 @@ use imt::circuits::mutate::IMTMutate;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::{keyspace_key_from_storage_hash, Hash};
+use crate::{keyspace_key_from_storage_hash, record_public_values, time_bounds::TimeBounds, Hash};
 
 pub type Sp1ProofVerify = fn(&[u32; 8], &Hash);
 
@@ -12,6 +12,10 @@ pub struct SP1Proof {
     pub record_vk_hash: Hash,
     /// The storage hash.
     pub storage_hash: Hash,
+    /// The time bounds committed by the record proof, if any.
+    pub time_bounds: Option<TimeBounds>,
+    /// The Keyspace root committed by the record proof, if any.
+    pub keyspace_root: Option<Hash>,
 }
 
 impl SP1Proof {
@@ -32,10 +36,13 @@ impl SP1Proof {
             "record_vk_hash does not match with current_key"
         );
 
-        let mut pub_inputs = [0; 96];
-        pub_inputs[..32].copy_from_slice(&keyspace_id);
-        pub_inputs[32..64].copy_from_slice(&current_key);
-        pub_inputs[64..].copy_from_slice(&new_key);
+        let pub_inputs = record_public_values(
+            &keyspace_id,
+            &current_key,
+            &new_key,
+            self.time_bounds.as_ref(),
+            self.keyspace_root.as_ref(),
+        );
 
         let public_values_digest = Sha256::digest(pub_inputs);
 
@@ -84,6 +91,8 @@ mod tests {
         let sut = SP1Proof {
             record_vk_hash: [0xaa; 32],
             storage_hash,
+            time_bounds: None,
+            keyspace_root: None,
         };
 
         let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
@@ -108,6 +117,8 @@ mod tests {
         let sut = SP1Proof {
             record_vk_hash,
             storage_hash,
+            time_bounds: None,
+            keyspace_root: None,
         };
 
         let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
@@ -141,6 +152,8 @@ mod tests {
         let sut = SP1Proof {
             record_vk_hash: [0xaa; 32],
             storage_hash: old_storage_hash,
+            time_bounds: None,
+            keyspace_root: None,
         };
 
         let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
@@ -173,6 +186,8 @@ mod tests {
         let sut = SP1Proof {
             record_vk_hash,
             storage_hash: old_storage_hash,
+            time_bounds: None,
+            keyspace_root: None,
         };
 
         let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
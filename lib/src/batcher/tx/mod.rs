@@ -47,6 +47,27 @@ impl Tx {
         }
     }
 
+    /// Returns the Keyspace root committed by the tx record proof, if any.
+    pub fn keyspace_root(&self) -> Option<&Hash> {
+        match self {
+            Tx::Offchain(offchain) => offchain.proof.keyspace_root(),
+            Tx::Onchain(onchain) => onchain.proof.keyspace_root.as_ref(),
+        }
+    }
+
+    /// Returns the Keyspace id and the new key set by the tx.
+    pub fn keyspace_update(&self) -> (Hash, Hash) {
+        let imt_mutate = match self {
+            Tx::Offchain(offchain) => &offchain.imt_mutate,
+            Tx::Onchain(onchain) => &onchain.imt_mutate,
+        };
+
+        match imt_mutate {
+            IMTMutate::Insert(insert) => (insert.node.key, insert.node.value),
+            IMTMutate::Update(update) => (update.node.key, update.new_value),
+        }
+    }
+
     pub fn verify_imt_mutate(&self, old_root: &Hash) -> Result<Hash> {
         match self {
             Tx::Offchain(offchain) => offchain.imt_mutate.verify(Keccak::v256, *old_root),
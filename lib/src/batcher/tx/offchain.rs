@@ -34,7 +34,14 @@ impl OffchainTx {
         hash
     }
 
-    pub fn process_proof(&self, sp1_verify: Sp1ProofVerify) {
+    pub fn process_proof(&self, timestamp: u64, sp1_verify: Sp1ProofVerify) {
+        if let Some(time_bounds) = self.proof.time_bounds() {
+            assert!(
+                time_bounds.contains(timestamp),
+                "record proof is not valid at the batch timestamp"
+            );
+        }
+
         match &self.proof {
             Proof::SP1(proof) => {
                 proof.commit_to_proof(&self.imt_mutate, sp1_verify);
@@ -54,7 +61,9 @@ mod tests {
 
     use imt::circuits::imt::Imt;
 
-    use crate::batcher::proof::sp1::SP1Proof;
+    use crate::{
+        batcher::proof::sp1::SP1Proof, keyspace_key_from_storage_hash, time_bounds::TimeBounds,
+    };
 
     use super::*;
 
@@ -72,6 +81,8 @@ mod tests {
             proof: Proof::SP1(SP1Proof {
                 record_vk_hash: [0; 32],
                 storage_hash: [0; 32],
+                time_bounds: None,
+                keyspace_root: None,
             }),
         };
         let hash = sut.hash();
@@ -102,6 +113,8 @@ mod tests {
             proof: Proof::SP1(SP1Proof {
                 record_vk_hash: [0; 32],
                 storage_hash: [0; 32],
+                time_bounds: None,
+                keyspace_root: None,
             }),
         };
 
@@ -116,4 +129,37 @@ mod tests {
 
         assert_eq!(hash, expected_hash);
     }
+
+    fn time_bounded_tx(time_bounds: TimeBounds) -> OffchainTx {
+        let mut imt = Imt::new(Keccak::v256);
+
+        let record_vk_hash = [16; 32];
+        let storage_hash = [42; 32];
+        let keyspace_id = keyspace_key_from_storage_hash(&record_vk_hash, &storage_hash);
+        let insert = imt.insert_node(keyspace_id, [43; 32]);
+
+        OffchainTx {
+            imt_mutate: insert,
+            prev_tx_hash: [0xff; 32],
+            proof: Proof::sp1(record_vk_hash, storage_hash, Some(time_bounds), None),
+        }
+    }
+
+    #[test]
+    fn test_process_proof_within_time_bounds() {
+        let sut = time_bounded_tx(TimeBounds::new(100, 200));
+
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+        sut.process_proof(100, sp1_verify);
+        sut.process_proof(200, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "record proof is not valid at the batch timestamp")]
+    fn test_process_proof_outside_time_bounds() {
+        let sut = time_bounded_tx(TimeBounds::new(100, 200));
+
+        let sp1_verify: Sp1ProofVerify = |_vk_hash, _public_values_digest| {};
+        sut.process_proof(201, sp1_verify);
+    }
 }
@@ -1,4 +1,8 @@
+use std::fmt;
+
 use imt::circuits::mutate::IMTMutate;
+use num_bigint::BigUint;
+use num_traits::Num;
 use serde::{Deserialize, Serialize};
 use tiny_keccak::{Hasher, Keccak};
 
@@ -32,11 +36,177 @@ impl OnchainTx {
         hash
     }
 
-    pub fn is_valid_record_proof(&self) -> bool {
-        self.proof.is_valid_record_proof(&self.imt_mutate)
+    /// Returns the hash of the update request, as enqueued by the L1 contract.
+    pub fn request_hash(&self) -> Hash {
+        let (keyspace_id, new_key) = match &self.imt_mutate {
+            IMTMutate::Insert(insert) => (insert.node.key, insert.node.value),
+            IMTMutate::Update(update) => (update.node.key, update.new_value),
+        };
+
+        request_hash(&keyspace_id, &new_key, &self.proof)
+    }
+
+    /// Returns the onchain queue hash once this tx is consumed from `queue_hash`.
+    pub fn next_queue_hash(&self, queue_hash: &Hash) -> Hash {
+        next_queue_hash(queue_hash, &self.request_hash())
+    }
+
+    /// Returns the result of the tx record proof at the batch `timestamp`.
+    ///
+    /// This does NOT check the Keyspace root read by the record proof (see
+    /// [crate::batcher::inputs::Inputs::onchain_tx_result]).
+    pub fn record_proof_result(&self, timestamp: u64) -> OnchainTxResult {
+        // Matched instead of `Option::is_none_or`, which the guest toolchain does not provide.
+        let in_time_bounds = match &self.proof.time_bounds {
+            Some(time_bounds) => time_bounds.contains(timestamp),
+            None => true,
+        };
+        if !in_time_bounds {
+            return OnchainTxResult::OutsideTimeBounds;
+        }
+
+        // A record proof from a record that does not control the id is invalid: check it first,
+        // as the PLONK proof verification asserts it.
+        let current_key = match &self.imt_mutate {
+            IMTMutate::Insert(insert) => insert.node.key,
+            IMTMutate::Update(update) => update.node.value,
+        };
+        if !self.proof.controls(&current_key) {
+            return OnchainTxResult::NotControlled;
+        }
+
+        match self.proof.is_valid_record_proof(&self.imt_mutate) {
+            true => OnchainTxResult::Applied,
+            false => OnchainTxResult::InvalidProof,
+        }
+    }
+}
+
+/// The result of an onchain tx, committed by the batcher program as a one byte reason code.
+///
+/// Onchain txs are forced by the L1 contract: instead of failing the batch, the ones with an
+/// invalid record proof are skipped (their IMTMutate is not applied).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[repr(u8)]
+pub enum OnchainTxResult {
+    /// The tx IMTMutate is applied.
+    Applied = 0,
+    /// The record proof reads the Keyspace from a root that is not accepted by the batch.
+    KeyspaceRootNotAccepted = 1,
+    /// The record proof time bounds do not contain the batch timestamp.
+    OutsideTimeBounds = 2,
+    /// The record behind the proof does not control the id current key.
+    NotControlled = 3,
+    /// The PLONK record proof is invalid.
+    InvalidProof = 4,
+    /// The record proof is a delegation approval, not an update (see [crate::approval_public_values]).
+    DelegationApproval = 5,
+}
+
+impl OnchainTxResult {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(Self::Applied),
+            1 => Some(Self::KeyspaceRootNotAccepted),
+            2 => Some(Self::OutsideTimeBounds),
+            3 => Some(Self::NotControlled),
+            4 => Some(Self::InvalidProof),
+            5 => Some(Self::DelegationApproval),
+            _ => None,
+        }
+    }
+
+    pub fn is_applied(self) -> bool {
+        self == Self::Applied
+    }
+}
+
+impl fmt::Display for OnchainTxResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::Applied => "applied",
+            Self::KeyspaceRootNotAccepted => "record proof Keyspace root is not accepted",
+            Self::OutsideTimeBounds => "record proof is not valid at the batch timestamp",
+            Self::NotControlled => "record does not control the Keyspace id",
+            Self::InvalidProof => "invalid record proof",
+            Self::DelegationApproval => "record proof is a delegation approval, not an update",
+        };
+        f.write_str(reason)
     }
 }
 
+/// Returns the hash of an update request, as computed by the L1 contract from the
+/// `KeyspaceUpdateRequested` event fields:
+///
+/// ```solidity
+/// keccak256(abi.encodePacked(
+///     keyspaceId, newKey, storageHash, plonkVkHash, zkvmVkHash,
+///     keccak256(extraPublicValues), keccak256(vk), keccak256(proof)
+/// ))
+/// ```
+///
+/// where `extraPublicValues` are the record time bounds and Keyspace root, if committed.
+pub fn request_hash(keyspace_id: &Hash, new_key: &Hash, proof: &PLONKProof) -> Hash {
+    let mut extra_public_values = vec![];
+    if let Some(time_bounds) = &proof.time_bounds {
+        extra_public_values.extend(time_bounds.to_bytes());
+    }
+    if let Some(keyspace_root) = &proof.keyspace_root {
+        extra_public_values.extend(keyspace_root);
+    }
+
+    let mut k = Keccak::v256();
+    k.update(keyspace_id);
+    k.update(new_key);
+    k.update(&proof.storage_hash);
+    k.update(&uint256(&proof.plonk_vk_hash));
+    k.update(&uint256(&proof.zkvm_vk_hash));
+    k.update(&keccak(&extra_public_values));
+    k.update(&keccak(&proof.vk));
+    k.update(&keccak(&proof.proof));
+
+    let mut hash = [0; 32];
+    k.finalize(&mut hash);
+    hash
+}
+
+/// Returns the onchain queue hash once the request with `request_hash` is enqueued (or consumed)
+/// from `queue_hash`: keccak(queue_hash || request_hash).
+pub fn next_queue_hash(queue_hash: &Hash, request_hash: &Hash) -> Hash {
+    let mut k = Keccak::v256();
+    k.update(queue_hash);
+    k.update(request_hash);
+
+    let mut hash = [0; 32];
+    k.finalize(&mut hash);
+    hash
+}
+
+/// Encodes a decimal number as a big endian uint256.
+fn uint256(decimal: &str) -> Hash {
+    let bytes = BigUint::from_str_radix(decimal, 10)
+        .expect("invalid decimal number")
+        .to_bytes_be();
+    assert!(bytes.len() <= 32, "number does not fit in a uint256");
+
+    let mut word = [0; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    word
+}
+
+fn keccak(bytes: &[u8]) -> Hash {
+    let mut k = Keccak::v256();
+    k.update(bytes);
+
+    let mut hash = [0; 32];
+    k.finalize(&mut hash);
+    hash
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -61,6 +231,8 @@ mod tests {
                 plonk_vk_hash: "0".to_string(),
                 zkvm_vk_hash: "0".to_string(),
                 storage_hash: [0xff; 32],
+                time_bounds: None,
+                keyspace_root: None,
             },
         };
         let hash = sut.hash();
@@ -95,6 +267,8 @@ mod tests {
                 plonk_vk_hash: "0".to_string(),
                 zkvm_vk_hash: "0".to_string(),
                 storage_hash: [0xff; 32],
+                time_bounds: None,
+                keyspace_root: None,
             },
         };
         let hash = offchain_tx.hash();
@@ -109,4 +283,73 @@ mod tests {
 
         assert_eq!(hash, expected_hash);
     }
+
+    #[test]
+    fn test_record_proof_not_controlling_the_id_is_invalid() {
+        let mut imt = Imt::new(Keccak::v256);
+        let insert = imt.insert_node([1; 32], [42; 32]);
+
+        let sut = OnchainTx {
+            imt_mutate: insert,
+            prev_tx_hash: [0xff; 32],
+            proof: PLONKProof {
+                vk: [0xff; 32].to_vec(),
+                proof: vec![1, 2, 3, 4, 5],
+                plonk_vk_hash: "0".to_string(),
+                zkvm_vk_hash: "0".to_string(),
+                storage_hash: [0xff; 32],
+                time_bounds: None,
+                keyspace_root: None,
+            },
+        };
+
+        assert_eq!(sut.record_proof_result(0), OnchainTxResult::NotControlled);
+    }
+
+    #[test]
+    fn test_next_queue_hash() {
+        let mut imt = Imt::new(Keccak::v256);
+        let insert = imt.insert_node([1; 32], [42; 32]);
+
+        let sut = OnchainTx {
+            imt_mutate: insert,
+            prev_tx_hash: [0xff; 32],
+            proof: PLONKProof {
+                vk: vec![0xaa; 4],
+                proof: vec![0xbb; 40],
+                plonk_vk_hash: "42".to_string(),
+                zkvm_vk_hash: "256".to_string(),
+                storage_hash: [0x13; 32],
+                time_bounds: None,
+                keyspace_root: None,
+            },
+        };
+
+        let mut expected_keccak = Keccak::v256();
+        expected_keccak.update(&[1; 32]);
+        expected_keccak.update(&[42; 32]);
+        expected_keccak.update(&[0x13; 32]);
+        expected_keccak.update(&[[0; 31].as_slice(), &[42]].concat());
+        expected_keccak.update(&[[0; 30].as_slice(), &[1, 0]].concat());
+        expected_keccak.update(&keccak(&[]));
+        expected_keccak.update(&keccak(&[0xaa; 4]));
+        expected_keccak.update(&keccak(&[0xbb; 40]));
+        let mut request_hash = [0u8; 32];
+        expected_keccak.finalize(&mut request_hash);
+        assert_eq!(sut.request_hash(), request_hash);
+
+        let queue_hash = sut.next_queue_hash(&[0; 32]);
+        assert_eq!(queue_hash, keccak(&[[0; 32], request_hash].concat()));
+        assert_ne!(sut.next_queue_hash(&queue_hash), queue_hash);
+    }
+
+    #[test]
+    fn test_result_codes() {
+        for code in 0..=5 {
+            let result = OnchainTxResult::from_code(code).unwrap();
+            assert_eq!(result.code(), code);
+            assert_eq!(result.is_applied(), code == 0);
+        }
+        assert_eq!(OnchainTxResult::from_code(6), None);
+    }
 }
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+use super::storage::Storage;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the record storage.
+    pub storage: Storage,
+    /// Private input: the 65 bytes BIP-137 signature (`header || r || s`) over the update message.
+    pub sig: Vec<u8>,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
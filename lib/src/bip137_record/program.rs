@@ -0,0 +1,173 @@
+use sha2::{Digest, Sha256};
+
+use crate::{ecdsa_record::k_signature::KSignature, keyspace_key_from_storage, Hash};
+
+use super::{
+    inputs::Inputs,
+    storage::{hash160, AddressKind},
+};
+
+/// The prefix of Bitcoin signed messages.
+const MESSAGE_PREFIX: &[u8] = b"\x18Bitcoin Signed Message:\n";
+
+pub struct Program;
+
+impl Program {
+    pub fn run(inputs: &Inputs) {
+        // Ensure the provided storage is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.storage.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "storage does not match with current_key"
+        );
+
+        assert_eq!(inputs.sig.len(), 65, "invalid signature length");
+
+        // The BIP-137 header encodes the recovery id and the address type. Wallets using the P2PKH
+        // compressed header for P2WPKH addresses are also supported.
+        let header = inputs.sig[0];
+        let (recid, compressed) = match (inputs.storage.kind, header) {
+            (AddressKind::P2PKH, 27..=30) => (header - 27, false),
+            (AddressKind::P2PKH | AddressKind::P2WPKH, 31..=34) => (header - 31, true),
+            (AddressKind::P2WPKH, 39..=42) => (header - 39, true),
+            _ => panic!("invalid signature header for the address type"),
+        };
+
+        // Recover the public key from the signature and the update message.
+        let msg_hash = Self::msg_hash(&Self::message(&inputs.keyspace_id, &inputs.new_key));
+        let sig = KSignature {
+            sig: inputs.sig[1..].try_into().unwrap(),
+            recid,
+        };
+        let pub_key = sig
+            .ecrecover(&msg_hash)
+            .expect("failed to recover the public key");
+
+        // Ensure the public key matches with the address.
+        let pub_key = match compressed {
+            true => [&[2 + (pub_key[63] & 1)], &pub_key[..32]].concat(),
+            false => [&[4], pub_key.as_slice()].concat(),
+        };
+        assert_eq!(
+            hash160(&pub_key),
+            inputs.storage.pubkey_hash,
+            "signer does not match the address"
+        );
+    }
+
+    /// Returns the update message to sign with a Bitcoin wallet.
+    pub fn message(keyspace_id: &Hash, new_key: &Hash) -> String {
+        let hex = |bytes: &Hash| -> String { bytes.iter().map(|b| format!("{b:02x}")).collect() };
+
+        format!(
+            "Keyspace update\nkeyspace_id: 0x{}\nnew_key: 0x{}",
+            hex(keyspace_id),
+            hex(new_key)
+        )
+    }
+
+    /// Computes the Bitcoin signed message hash: sha256(sha256(prefix, varint(len), message)).
+    pub fn msg_hash(message: &str) -> Hash {
+        let len = message.len();
+        let varint = match len {
+            0..=0xfc => vec![len as u8],
+            0xfd..=0xffff => [&[0xfd], (len as u16).to_le_bytes().as_slice()].concat(),
+            _ => [&[0xfe], (len as u32).to_le_bytes().as_slice()].concat(),
+        };
+
+        let hash = Sha256::new()
+            .chain_update(MESSAGE_PREFIX)
+            .chain_update(varint)
+            .chain_update(message)
+            .finalize();
+        Sha256::digest(hash).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{bip137_record::storage::Storage, test_utils::signing_key_from_int};
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+
+    // The addresses of the private key 1.
+    const P2PKH_UNCOMPRESSED: &str = "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm";
+    const P2PKH_COMPRESSED: &str = "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH";
+    const P2WPKH: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    /// Returns the inputs for the `address` record, signed by the key `seed` with the header offset
+    /// `header_base` (27, 31 or 39).
+    fn inputs(address: &str, seed: u8, header_base: u8) -> Inputs {
+        let storage = Storage::from_address(address).unwrap();
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &storage.to_bytes());
+        let new_key = [42; 32];
+
+        let msg_hash = Program::msg_hash(&Program::message(&keyspace_id, &new_key));
+        let (sig, recid) = signing_key_from_int(seed)
+            .sign_prehash_recoverable(&msg_hash)
+            .unwrap();
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            storage,
+            sig: [vec![header_base + recid.to_byte()], sig.to_bytes().to_vec()].concat(),
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run_p2pkh_uncompressed() {
+        Program::run(&inputs(P2PKH_UNCOMPRESSED, 1, 27));
+    }
+
+    #[test]
+    fn test_run_p2pkh_compressed() {
+        Program::run(&inputs(P2PKH_COMPRESSED, 1, 31));
+    }
+
+    #[test]
+    fn test_run_p2wpkh() {
+        Program::run(&inputs(P2WPKH, 1, 39));
+    }
+
+    #[test]
+    fn test_run_p2wpkh_compressed_header() {
+        Program::run(&inputs(P2WPKH, 1, 31));
+    }
+
+    #[test]
+    #[should_panic(expected = "signer does not match the address")]
+    fn test_run_p2pkh_invalid_compression() {
+        Program::run(&inputs(P2PKH_COMPRESSED, 1, 27));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature header for the address type")]
+    fn test_run_p2wpkh_uncompressed_header() {
+        Program::run(&inputs(P2WPKH, 1, 27));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature header for the address type")]
+    fn test_run_p2sh_p2wpkh_header() {
+        Program::run(&inputs(P2WPKH, 1, 35));
+    }
+
+    #[test]
+    #[should_panic(expected = "signer does not match the address")]
+    fn test_run_invalid_signer() {
+        Program::run(&inputs(P2WPKH, 2, 39));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(P2WPKH, 1, 39);
+        inputs.storage = Storage::from_address(P2PKH_COMPRESSED).unwrap();
+        Program::run(&inputs);
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::{bail, ensure, Context, Result};
+use bech32::{hrp, segwit, Fe32};
+use ripemd::Ripemd160;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The P2PKH address version byte (mainnet).
+const P2PKH_VERSION: u8 = 0x00;
+
+/// The type of a Bitcoin address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AddressKind {
+    /// A legacy pay-to-pubkey-hash address (`1...`).
+    P2PKH,
+    /// A native SegWit v0 pay-to-witness-pubkey-hash address (`bc1q...`).
+    P2WPKH,
+}
+
+/// The storage of a BIP-137 record: a Bitcoin address.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Storage {
+    pub kind: AddressKind,
+    /// The hash160 of the public key.
+    pub pubkey_hash: [u8; 20],
+}
+
+impl Storage {
+    /// Encodes the storage as kind (0 for P2PKH, 1 for P2WPKH) || pubkey_hash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(21);
+        bytes.push(match self.kind {
+            AddressKind::P2PKH => 0,
+            AddressKind::P2WPKH => 1,
+        });
+        bytes.extend_from_slice(&self.pubkey_hash);
+        bytes
+    }
+
+    /// Parses a mainnet P2PKH (base58check) or P2WPKH (bech32) address.
+    pub fn from_address(address: &str) -> Result<Self> {
+        if let Ok((hrp, version, program)) = segwit::decode(address) {
+            ensure!(hrp == hrp::BC, "unsupported network: {hrp}");
+            ensure!(
+                version == Fe32::Q && program.len() == 20,
+                "unsupported SegWit address"
+            );
+
+            return Ok(Self {
+                kind: AddressKind::P2WPKH,
+                pubkey_hash: program.try_into().unwrap(),
+            });
+        }
+
+        let payload = bs58::decode(address)
+            .with_check(None)
+            .into_vec()
+            .context("invalid address")?;
+        match payload.split_first() {
+            Some((&P2PKH_VERSION, pubkey_hash)) if pubkey_hash.len() == 20 => Ok(Self {
+                kind: AddressKind::P2PKH,
+                pubkey_hash: pubkey_hash.try_into().unwrap(),
+            }),
+            _ => bail!("unsupported address"),
+        }
+    }
+
+    /// Encodes the storage as a mainnet address.
+    pub fn to_address(&self) -> String {
+        match self.kind {
+            AddressKind::P2PKH => {
+                bs58::encode([&[P2PKH_VERSION], self.pubkey_hash.as_slice()].concat())
+                    .with_check()
+                    .into_string()
+            }
+            AddressKind::P2WPKH => segwit::encode_v0(hrp::BC, &self.pubkey_hash).unwrap(),
+        }
+    }
+}
+
+/// Computes the hash160 of `data`: ripemd160(sha256(data)).
+pub fn hash160(data: &[u8]) -> [u8; 20] {
+    Ripemd160::digest(Sha256::digest(data)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::signing_key_from_int;
+
+    use super::*;
+
+    // The addresses of the private key 1.
+    const P2PKH_UNCOMPRESSED: &str = "1EHNa6Q4Jz2uvNExL497mE43ikXhwF6kZm";
+    const P2PKH_COMPRESSED: &str = "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH";
+    const P2WPKH: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    #[test]
+    fn test_address_roundtrip() {
+        for address in [P2PKH_UNCOMPRESSED, P2PKH_COMPRESSED, P2WPKH] {
+            assert_eq!(
+                Storage::from_address(address).unwrap().to_address(),
+                address
+            );
+        }
+
+        let pub_key = signing_key_from_int(1)
+            .verifying_key()
+            .to_encoded_point(true);
+        assert_eq!(
+            Storage::from_address(P2WPKH).unwrap().pubkey_hash,
+            hash160(pub_key.as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_address_invalid_checksum() {
+        assert!(Storage::from_address("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMJ").is_err());
+        assert!(Storage::from_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err());
+    }
+}
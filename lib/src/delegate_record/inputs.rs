@@ -0,0 +1,31 @@
+use imt::circuits::mutate::IMTMutate;
+use serde::{Deserialize, Serialize};
+
+use crate::{time_bounds::TimeBounds, Hash};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+    /// Public input: the Keyspace root the parent current key is read from.
+    pub keyspace_root: Hash,
+
+    /// Private input: the parent Keyspace id, which is the record storage.
+    pub parent_id: Hash,
+    /// Private input: the inclusion proof of the parent node in the `keyspace_root`.
+    ///
+    /// It is an IMT update of the parent node that leaves its value (the parent current key) unchanged.
+    pub parent_inclusion: IMTMutate<Hash, Hash>,
+    /// Private input: the parent record verifier key hash.
+    pub parent_vk_hash: Hash,
+    /// Private input: the parent record storage hash.
+    pub parent_storage_hash: Hash,
+    /// Private input: the time bounds committed by the parent record proof, if any.
+    pub parent_time_bounds: Option<TimeBounds>,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
@@ -0,0 +1,8 @@
+//! The delegate record: a Keyspace id controlled by another (parent) Keyspace id.
+//!
+//! The updates are approved by a delegation approval of the parent (see
+//! [crate::approval_public_values]), which only the record programs able to commit one can
+//! produce: the ECDSA and Schnorr records. The other records can not be parents.
+
+pub mod inputs;
+pub mod program;
@@ -0,0 +1,247 @@
+use imt::circuits::mutate::IMTMutate;
+use sha2::{Digest, Sha256};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    approval_public_values,
+    batcher::proof::sp1::{bytes_to_words_be, Sp1ProofVerify},
+    keyspace_key_from_storage, keyspace_key_from_storage_hash,
+    time_bounds::TimeBounds,
+    Hash,
+};
+
+use super::inputs::Inputs;
+
+pub struct Program;
+
+impl Program {
+    /// Runs the delegate record program and returns the time bounds the record proof must commit to, if any.
+    ///
+    /// The update is approved by a record proof of the parent, generated by the parent record program
+    /// as a delegation approval (see [approval_public_values]) of the [Program::delegation_key].
+    pub fn run(inputs: &Inputs, sp1_verify: Sp1ProofVerify) -> Option<TimeBounds> {
+        // Ensure the provided parent id is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.parent_id);
+        assert_eq!(
+            inputs.current_key, current_key,
+            "parent_id does not match with current_key"
+        );
+
+        // Read the parent current key from the Keyspace root.
+        let IMTMutate::Update(update) = &inputs.parent_inclusion else {
+            panic!("invalid parent inclusion proof");
+        };
+        assert_eq!(update.node.key, inputs.parent_id, "invalid parent node");
+        assert_eq!(
+            update.new_value, update.node.value,
+            "invalid parent inclusion proof"
+        );
+        let root = inputs
+            .parent_inclusion
+            .verify(Keccak::v256, inputs.keyspace_root)
+            .expect("invalid parent inclusion proof");
+        assert_eq!(root, inputs.keyspace_root, "invalid parent inclusion proof");
+        let parent_key = update.node.value;
+
+        // Ensure the provided `parent_vk_hash` is the one controlling the parent id.
+        assert_eq!(
+            parent_key,
+            keyspace_key_from_storage_hash(&inputs.parent_vk_hash, &inputs.parent_storage_hash),
+            "parent_vk_hash does not match with the parent current key"
+        );
+
+        // Verify the parent record proof approving this update.
+        let pub_inputs = approval_public_values(
+            &inputs.parent_id,
+            &parent_key,
+            &Self::delegation_key(&inputs.keyspace_id, &inputs.new_key),
+            inputs.parent_time_bounds.as_ref(),
+        );
+        let public_values_digest = Sha256::digest(pub_inputs);
+        let vk_hash = bytes_to_words_be(&inputs.parent_vk_hash)
+            .try_into()
+            .expect("failed to convert vk hash");
+        sp1_verify(&vk_hash, &public_values_digest.into());
+
+        // The update is only valid when the parent approval is.
+        inputs.parent_time_bounds
+    }
+
+    /// Computes the key the parent record proof must approve, to approve the update of the delegated
+    /// `keyspace_id` to `new_key`: keccack("KEYSPACE_DELEGATION", keyspace_id, new_key).
+    pub fn delegation_key(keyspace_id: &Hash, new_key: &Hash) -> Hash {
+        let mut k = Keccak::v256();
+        let mut delegation_key = [0; 32];
+        k.update(b"KEYSPACE_DELEGATION");
+        k.update(keyspace_id);
+        k.update(new_key);
+        k.finalize(&mut delegation_key);
+        delegation_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use imt::circuits::imt::Imt;
+
+    use crate::{hash_storage, record_public_values};
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+    const PARENT_VK_HASH: Hash = [0xbb; 32];
+    const PARENT_STORAGE: Hash = [1; 32];
+
+    thread_local! {
+        static VERIFIED: RefCell<Option<([u32; 8], Hash)>> = const { RefCell::new(None) };
+    }
+
+    fn sp1_verify(vk_hash: &[u32; 8], public_values_digest: &Hash) {
+        VERIFIED.with(|verified| *verified.borrow_mut() = Some((*vk_hash, *public_values_digest)));
+    }
+
+    fn parent_key() -> Hash {
+        keyspace_key_from_storage(&PARENT_VK_HASH, &PARENT_STORAGE)
+    }
+
+    /// Returns the inputs to update the id delegated to the parent [2; 32], read from a Keyspace where
+    /// the parent has the key `parent_key`.
+    fn inputs(parent_key: Hash, parent_time_bounds: Option<TimeBounds>) -> Inputs {
+        let parent_id = [2; 32];
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &parent_id);
+
+        let mut imt = Imt::new(Keccak::v256);
+        imt.insert_node(parent_id, parent_key);
+        imt.insert_node(keyspace_id, keyspace_id);
+        let parent_inclusion = imt.update_node(parent_id, parent_key);
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key: [42; 32],
+            keyspace_root: imt.root,
+            parent_id,
+            parent_inclusion,
+            parent_vk_hash: PARENT_VK_HASH,
+            parent_storage_hash: hash_storage(&PARENT_STORAGE),
+            parent_time_bounds,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    fn expected_verification(inputs: &Inputs) -> ([u32; 8], Hash) {
+        let pub_inputs = approval_public_values(
+            &inputs.parent_id,
+            &parent_key(),
+            &Program::delegation_key(&inputs.keyspace_id, &inputs.new_key),
+            inputs.parent_time_bounds.as_ref(),
+        );
+
+        (
+            bytes_to_words_be(&PARENT_VK_HASH).try_into().unwrap(),
+            Sha256::digest(pub_inputs).into(),
+        )
+    }
+
+    #[test]
+    fn test_run() {
+        let inputs = inputs(parent_key(), None);
+
+        assert_eq!(Program::run(&inputs, sp1_verify), None);
+        assert_eq!(
+            VERIFIED.with(|verified| verified.take()),
+            Some(expected_verification(&inputs))
+        );
+    }
+
+    /// Verifies the parent record proof as a plain update of the parent to the delegation key, as
+    /// committed by the parent records unable to approve.
+    fn sp1_verify_parent_update(_vk_hash: &[u32; 8], public_values_digest: &Hash) {
+        let inputs = inputs(parent_key(), None);
+        let pub_inputs = record_public_values(
+            &inputs.parent_id,
+            &parent_key(),
+            &Program::delegation_key(&inputs.keyspace_id, &inputs.new_key),
+            None,
+            None,
+        );
+        assert_eq!(
+            *public_values_digest,
+            Hash::from(Sha256::digest(pub_inputs)),
+            "invalid parent record proof"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid parent record proof")]
+    fn test_run_parent_update_is_not_an_approval() {
+        Program::run(&inputs(parent_key(), None), sp1_verify_parent_update);
+    }
+
+    #[test]
+    fn test_run_forwards_parent_time_bounds() {
+        let time_bounds = TimeBounds::until(1_000);
+        let inputs = inputs(parent_key(), Some(time_bounds));
+
+        assert_eq!(Program::run(&inputs, sp1_verify), Some(time_bounds));
+        assert_eq!(
+            VERIFIED.with(|verified| verified.take()),
+            Some(expected_verification(&inputs))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid parent node")]
+    fn test_run_invalid_parent_node() {
+        let mut inputs = inputs(parent_key(), None);
+
+        let mut imt = Imt::new(Keccak::v256);
+        imt.insert_node([3; 32], parent_key());
+        inputs.parent_inclusion = imt.update_node([3; 32], parent_key());
+        inputs.keyspace_root = imt.root;
+
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid parent inclusion proof")]
+    fn test_run_parent_value_changed() {
+        let mut inputs = inputs(parent_key(), None);
+
+        let mut imt = Imt::new(Keccak::v256);
+        imt.insert_node(inputs.parent_id, [5; 32]);
+        inputs.parent_inclusion = imt.update_node(inputs.parent_id, parent_key());
+
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid parent inclusion proof")]
+    fn test_run_invalid_keyspace_root() {
+        let mut inputs = inputs(parent_key(), None);
+        inputs.keyspace_root = [7; 32];
+
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "parent_vk_hash does not match with the parent current key")]
+    fn test_run_invalid_parent_vk_hash() {
+        let inputs = inputs(
+            keyspace_key_from_storage(&[0xcc; 32], &PARENT_STORAGE),
+            None,
+        );
+        Program::run(&inputs, sp1_verify);
+    }
+
+    #[test]
+    #[should_panic(expected = "parent_id does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(parent_key(), None);
+        inputs.parent_id = [3; 32];
+
+        Program::run(&inputs, sp1_verify);
+    }
+}
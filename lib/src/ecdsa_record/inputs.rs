@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use crate::Hash;
+use crate::{time_bounds::TimeBounds, Hash};
 
 use super::k_signature::KSignature;
 
@@ -13,8 +13,15 @@ pub struct Inputs {
     /// Public input: the Keyspace new key.
     pub new_key: Hash,
 
-    /// Private input: the signature over keccak(keyspace_id, new_key).
+    /// Private input: the signature over keccak(keyspace_id, new_key [, valid_after, valid_until] [, DELEGATION_APPROVAL]).
     pub sig: KSignature,
+    /// Private input: the optional salt of the storage (see [super::storage::storage]).
+    pub salt: Option<Hash>,
+    /// Private input: the optional time bounds of the update, committed next to the public inputs.
+    pub time_bounds: Option<TimeBounds>,
+    /// Private input: whether the proof approves a delegated update (see [crate::approval_public_values])
+    /// instead of updating the Keyspace id.
+    pub delegation_approval: bool,
     // TODO: Could it be passed at compile time? Should we enforce it somehow and how?
     /// Private input: the verifier key hash.
     pub vk_hash: Hash,
@@ -1,5 +1,10 @@
+use anyhow::{anyhow, bail, Context, Result};
 use k256::ecdsa::{RecoveryId, Signature, VerifyingKey};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tiny_keccak::{Hasher, Keccak};
+
+/// An Ethereum address, as recovered by [KSignature::ecrecover_address].
+pub type Address = [u8; 20];
 
 #[derive(Debug)]
 pub struct KSignature {
@@ -26,32 +31,232 @@ impl<'de> Deserialize<'de> for KSignature {
     {
         let bytes: &[u8] = Deserialize::deserialize(deserializer)?;
 
-        if bytes.len() != 65 {
+        if bytes.len() != 64 && bytes.len() != 65 {
             return Err(serde::de::Error::invalid_length(
                 bytes.len(),
-                &"expected 65 bytes",
+                &"expected 64 or 65 bytes",
             ));
         }
 
-        let mut sig = [0u8; 64];
-        sig.copy_from_slice(&bytes[..64]);
-        let recid = bytes[64];
-
-        Ok(KSignature { sig, recid })
+        KSignature::from_bytes(bytes).map_err(serde::de::Error::custom)
     }
 }
 
 impl KSignature {
-    pub fn ecrecover(&self, msg: &[u8; 32]) -> [u8; 64] {
-        VerifyingKey::recover_from_prehash(
-            msg,
-            &Signature::from_slice(&self.sig).expect("failed sig"),
-            RecoveryId::from_byte(self.recid).expect("failed sig"),
-        )
-        .expect("failed recover_from_prehash")
-        .to_encoded_point(false)
-        .as_bytes()[1..]
+    /// Builds a [KSignature] from its raw bytes.
+    ///
+    /// Accepts either a 65 bytes `r || s || v` signature (see [KSignature::from_rsv] for the supported `v`
+    /// values) or a 64 bytes EIP-2098 compact signature (see [KSignature::from_compact]).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        match bytes.len() {
+            64 => Self::from_compact(bytes.try_into().unwrap()),
+            65 => Self::from_rsv(
+                bytes[..32].try_into().unwrap(),
+                bytes[32..64].try_into().unwrap(),
+                bytes[64] as u64,
+            ),
+            len => bail!("invalid signature length: expected 64 or 65 bytes, got {len}"),
+        }
+    }
+
+    /// Builds a [KSignature] from its `r`, `s` and `v` components.
+    ///
+    /// `v` can either be a raw recovery id (0/1), an Ethereum legacy value (27/28) or an EIP-155 value
+    /// (`chain_id * 2 + 35 + recid`). High `s` values are normalized (see [KSignature::normalize_s]).
+    pub fn from_rsv(r: &[u8; 32], s: &[u8; 32], v: u64) -> Result<Self> {
+        let recid = match v {
+            0 | 1 => v as u8,
+            27 | 28 => (v - 27) as u8,
+            v if v >= 35 => ((v - 35) % 2) as u8,
+            v => bail!("invalid signature v value: {v}"),
+        };
+
+        let mut sig = [0; 64];
+        sig[..32].copy_from_slice(r);
+        sig[32..].copy_from_slice(s);
+
+        let mut k_sig = Self { sig, recid };
+        k_sig.normalize_s()?;
+        Ok(k_sig)
+    }
+
+    /// Builds a [KSignature] from an EIP-2098 compact signature: `r || yParityAndS`, where the top bit of
+    /// `yParityAndS` is the recovery id.
+    pub fn from_compact(bytes: &[u8; 64]) -> Result<Self> {
+        let mut s: [u8; 32] = bytes[32..].try_into().unwrap();
+        let y_parity = s[0] >> 7;
+        s[0] &= 0x7f;
+
+        Self::from_rsv(bytes[..32].try_into().unwrap(), &s, y_parity as u64)
+    }
+
+    /// Normalizes the signature to its low `s` form, flipping the recovery id accordingly.
+    ///
+    /// Both (`s`, `recid`) and (`n - s`, `recid ^ 1`) recover the same public key, but only the low `s` form
+    /// is accepted by most verifiers (EIP-2).
+    pub fn normalize_s(&mut self) -> Result<()> {
+        let sig = Signature::from_slice(&self.sig).context("invalid signature")?;
+
+        if let Some(normalized) = sig.normalize_s() {
+            self.sig = normalized.to_bytes().into();
+            self.recid ^= 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn ecrecover(&self, msg: &[u8; 32]) -> Result<[u8; 64]> {
+        let sig = Signature::from_slice(&self.sig).context("invalid signature")?;
+        let recid = RecoveryId::from_byte(self.recid)
+            .ok_or_else(|| anyhow!("invalid recovery id: {}", self.recid))?;
+
+        let pub_key = VerifyingKey::recover_from_prehash(msg, &sig, recid)
+            .context("failed to recover the public key")?
+            .to_encoded_point(false);
+
+        Ok(pub_key.as_bytes()[1..]
             .try_into()
-            .expect("failed to convert pubkey")
+            .expect("failed to convert pubkey"))
+    }
+
+    /// Recovers the Ethereum address of the signer: keccak(pub_key)[12..].
+    pub fn ecrecover_address(&self, msg: &[u8; 32]) -> Result<Address> {
+        let pub_key = self.ecrecover(msg)?;
+
+        let mut k = Keccak::v256();
+        let mut pub_key_hash = [0; 32];
+        k.update(&pub_key);
+        k.finalize(&mut pub_key_hash);
+
+        Ok(pub_key_hash[12..].try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the hash signed by `eth_sign` / `personal_sign` for `msg`.
+    fn eth_signed_message_hash(msg: &[u8]) -> [u8; 32] {
+        let mut k = Keccak::v256();
+        k.update(format!("\x19Ethereum Signed Message:\n{}", msg.len()).as_bytes());
+        k.update(msg);
+
+        let mut hash = [0; 32];
+        k.finalize(&mut hash);
+        hash
+    }
+
+    fn hex_decode<const N: usize>(s: &str) -> [u8; N] {
+        let s = s.trim_start_matches("0x");
+        (0..N)
+            .map(|i| u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap())
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+
+    // `eth_sign("Some data")` with the private key 0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318.
+    const SOME_DATA_SIG: &str = "0xb91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c";
+    const SOME_DATA_SIGNER: &str = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+
+    // EIP-2098 test vectors: `eth_sign("Hello World")` and `eth_sign("It's a small(er) world")` with the private key
+    // 0x1234567890123456789012345678901234567890123456789012345678901234.
+    const EIP_2098_SIGNER: &str = "0x2e988a386a799f506693793c6a5af6b54dfaabfb";
+    const HELLO_WORLD_R: &str =
+        "0x68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b90";
+    const HELLO_WORLD_S: &str =
+        "0x7e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea52064";
+    const HELLO_WORLD_COMPACT: &str = "0x68a020a209d3d56c46f38cc50a33f704f4a9a10a59377f8dd762ac66910e9b907e865ad05c4035ab5792787d4a0297a43617ae897930a6fe4d822b8faea52064";
+    const SMALLER_WORLD_COMPACT: &str = "0x9328da16089fcba9bececa81663203989f2df5fe1faa6291a45381c81bd17f76939c6d6b623b42da56557e5e734a43dc83345ddfadec52cbe24d0cc64f550793";
+
+    #[test]
+    fn test_from_bytes_eth_sign() {
+        let sig = KSignature::from_bytes(&hex_decode::<65>(SOME_DATA_SIG)).unwrap();
+        assert_eq!(sig.recid, 1);
+
+        let address = sig
+            .ecrecover_address(&eth_signed_message_hash(b"Some data"))
+            .unwrap();
+        assert_eq!(address, hex_decode::<20>(SOME_DATA_SIGNER));
+    }
+
+    #[test]
+    fn test_from_rsv_v_values() {
+        let r = hex_decode::<32>(HELLO_WORLD_R);
+        let s = hex_decode::<32>(HELLO_WORLD_S);
+        let msg_hash = eth_signed_message_hash(b"Hello World");
+
+        // Raw recovery id, legacy and EIP-155 (chain id 1) encodings of the same signature.
+        for v in [0, 27, 37] {
+            let sig = KSignature::from_rsv(&r, &s, v).unwrap();
+            assert_eq!(sig.recid, 0);
+
+            let address = sig.ecrecover_address(&msg_hash).unwrap();
+            assert_eq!(address, hex_decode::<20>(EIP_2098_SIGNER));
+        }
+    }
+
+    #[test]
+    fn test_from_rsv_invalid_v() {
+        let r = hex_decode::<32>(HELLO_WORLD_R);
+        let s = hex_decode::<32>(HELLO_WORLD_S);
+
+        for v in [2, 26, 29, 34] {
+            assert!(KSignature::from_rsv(&r, &s, v).is_err());
+        }
+    }
+
+    #[test]
+    fn test_from_compact() {
+        for (msg, compact, recid) in [
+            (&b"Hello World"[..], HELLO_WORLD_COMPACT, 0),
+            (&b"It's a small(er) world"[..], SMALLER_WORLD_COMPACT, 1),
+        ] {
+            let msg_hash = eth_signed_message_hash(msg);
+            let sig = KSignature::from_bytes(&hex_decode::<64>(compact)).unwrap();
+            assert_eq!(sig.recid, recid);
+            assert_eq!(sig.sig[32] & 0x80, 0);
+
+            let address = sig.ecrecover_address(&msg_hash).unwrap();
+            assert_eq!(address, hex_decode::<20>(EIP_2098_SIGNER));
+        }
+    }
+
+    #[test]
+    fn test_normalize_high_s() {
+        let low_s = KSignature::from_bytes(&hex_decode::<65>(SOME_DATA_SIG)).unwrap();
+
+        // Build the malleable high `s` counterpart: (r, n - s, v ^ 1).
+        let sig = Signature::from_slice(&low_s.sig).unwrap();
+        let (r, s) = sig.split_scalars();
+        let high_s = Signature::from_scalars(r, -*s).unwrap();
+        let high_s_bytes: [u8; 64] = high_s.to_bytes().into();
+
+        let sig = KSignature::from_rsv(
+            high_s_bytes[..32].try_into().unwrap(),
+            high_s_bytes[32..].try_into().unwrap(),
+            (low_s.recid ^ 1) as u64,
+        )
+        .unwrap();
+
+        assert_eq!(sig.sig, low_s.sig);
+        assert_eq!(sig.recid, low_s.recid);
+    }
+
+    #[test]
+    fn test_ecrecover_invalid_signature() {
+        let sig = KSignature {
+            sig: [0; 64],
+            recid: 0,
+        };
+        assert!(sig.ecrecover(&[1; 32]).is_err());
+
+        let sig = KSignature::from_bytes(&hex_decode::<65>(SOME_DATA_SIG)).unwrap();
+        let sig = KSignature {
+            sig: sig.sig,
+            recid: 4,
+        };
+        assert!(sig.ecrecover(&[1; 32]).is_err());
     }
 }
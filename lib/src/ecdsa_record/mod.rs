@@ -1,3 +1,4 @@
 pub mod inputs;
 pub mod k_signature;
 pub mod program;
+pub mod storage;
@@ -1,27 +1,178 @@
 use tiny_keccak::{Hasher, Keccak};
 
-use crate::keyspace_key_from_storage;
+use crate::{keyspace_key_from_storage, time_bounds::TimeBounds, Hash, DELEGATION_APPROVAL};
 
-use super::inputs::Inputs;
+use super::{inputs::Inputs, storage::storage};
 
 pub struct Program;
 
 impl Program {
     pub fn run(inputs: &Inputs) {
-        // Compute the `msg_hash`: keccack(keyspace_id, new_key).
-        let mut k = Keccak::v256();
-        let mut msg_hash = [0; 32];
-        k.update(&inputs.keyspace_id);
-        k.update(&inputs.new_key);
-        k.finalize(&mut msg_hash);
+        let msg_hash = Self::msg_hash(
+            &inputs.keyspace_id,
+            &inputs.new_key,
+            inputs.time_bounds.as_ref(),
+            inputs.delegation_approval,
+        );
 
         // Recover the public key from the signature and `msg_hash`.
-        let recovered_pub_key = inputs.sig.ecrecover(&msg_hash);
+        let recovered_pub_key = inputs
+            .sig
+            .ecrecover(&msg_hash)
+            .expect("failed to recover the public key");
 
         // Recover the `current_key`: keccack(storage_hash, vk_hash).
-        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &recovered_pub_key);
+        let storage = storage(&recovered_pub_key, inputs.salt.as_ref());
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &storage);
 
         // Ensure the recovered `current_key` matches with the one passed as public input.
-        assert_eq!(inputs.current_key, current_key);
+        assert_eq!(
+            inputs.current_key, current_key,
+            "recovered storage does not match with current_key"
+        );
+    }
+
+    /// Computes the `msg_hash` to sign: keccack(keyspace_id, new_key [, valid_after, valid_until] [, DELEGATION_APPROVAL]).
+    ///
+    /// The time bounds are part of the signed message so that they can not be stripped or changed by the prover.
+    /// So is the [DELEGATION_APPROVAL] purpose word, so that an approval can not be proven as an update.
+    pub fn msg_hash(
+        keyspace_id: &Hash,
+        new_key: &Hash,
+        time_bounds: Option<&TimeBounds>,
+        delegation_approval: bool,
+    ) -> Hash {
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(keyspace_id);
+        k.update(new_key);
+        if let Some(time_bounds) = time_bounds {
+            k.update(&time_bounds.to_bytes());
+        }
+        if delegation_approval {
+            k.update(&DELEGATION_APPROVAL);
+        }
+        k.finalize(&mut msg_hash);
+
+        msg_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ecdsa_record::{k_signature::KSignature, storage::keyspace_id},
+        test_utils::{pub_key, signing_key},
+    };
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+
+    fn inputs(signed_time_bounds: Option<&TimeBounds>, time_bounds: Option<TimeBounds>) -> Inputs {
+        salted_inputs(None, signed_time_bounds, time_bounds)
+    }
+
+    fn salted_inputs(
+        salt: Option<Hash>,
+        signed_time_bounds: Option<&TimeBounds>,
+        time_bounds: Option<TimeBounds>,
+    ) -> Inputs {
+        signed_inputs(salt, signed_time_bounds, time_bounds, false)
+    }
+
+    fn signed_inputs(
+        salt: Option<Hash>,
+        signed_time_bounds: Option<&TimeBounds>,
+        time_bounds: Option<TimeBounds>,
+        signed_approval: bool,
+    ) -> Inputs {
+        let signing_key = signing_key(1);
+        let keyspace_id = keyspace_id(&VK_HASH, &pub_key(&signing_key), salt.as_ref());
+        let new_key = [42; 32];
+
+        let msg_hash =
+            Program::msg_hash(&keyspace_id, &new_key, signed_time_bounds, signed_approval);
+        let (sig, recid) = signing_key.sign_prehash_recoverable(&msg_hash).unwrap();
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            sig: KSignature {
+                sig: sig.to_bytes().into(),
+                recid: recid.to_byte(),
+            },
+            salt,
+            time_bounds,
+            delegation_approval: signed_approval,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        Program::run(&inputs(None, None));
+    }
+
+    #[test]
+    fn test_run_with_time_bounds() {
+        let time_bounds = TimeBounds::new(100, 200);
+        Program::run(&inputs(Some(&time_bounds), Some(time_bounds)));
+    }
+
+    #[test]
+    #[should_panic(expected = "recovered storage does not match with current_key")]
+    fn test_run_with_unsigned_time_bounds() {
+        Program::run(&inputs(None, Some(TimeBounds::new(100, 200))));
+    }
+
+    #[test]
+    #[should_panic(expected = "recovered storage does not match with current_key")]
+    fn test_run_with_stripped_time_bounds() {
+        Program::run(&inputs(Some(&TimeBounds::new(100, 200)), None));
+    }
+
+    #[test]
+    fn test_run_salted() {
+        Program::run(&salted_inputs(Some([7; 32]), None, None));
+    }
+
+    #[test]
+    fn test_run_delegation_approval() {
+        Program::run(&signed_inputs(None, None, None, true));
+    }
+
+    #[test]
+    #[should_panic(expected = "recovered storage does not match with current_key")]
+    fn test_run_delegation_approval_as_update() {
+        let mut inputs = signed_inputs(None, None, None, true);
+        inputs.delegation_approval = false;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "recovered storage does not match with current_key")]
+    fn test_run_invalid_salt() {
+        let mut inputs = salted_inputs(Some([7; 32]), None, None);
+        inputs.salt = Some([8; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_keyspace_id() {
+        let pub_key = pub_key(&signing_key(1));
+
+        // Unsalted storage is the public key, as before salts were supported.
+        assert_eq!(
+            keyspace_id(&VK_HASH, &pub_key, None),
+            keyspace_key_from_storage(&VK_HASH, &pub_key)
+        );
+
+        // Each salt derives an independent id.
+        assert_ne!(
+            keyspace_id(&VK_HASH, &pub_key, Some(&[1; 32])),
+            keyspace_id(&VK_HASH, &pub_key, Some(&[2; 32]))
+        );
     }
 }
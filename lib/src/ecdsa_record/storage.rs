@@ -0,0 +1,20 @@
+use crate::{keyspace_key_from_storage, Hash};
+
+/// Returns the ECDSA record storage: the uncompressed (64 bytes, without prefix) public key,
+/// optionally followed by a salt.
+///
+/// Salting the storage allows a single key to own several independent Keyspace ids.
+pub fn storage(pub_key: &[u8; 64], salt: Option<&Hash>) -> Vec<u8> {
+    let mut storage = Vec::with_capacity(96);
+    storage.extend_from_slice(pub_key);
+    if let Some(salt) = salt {
+        storage.extend_from_slice(salt);
+    }
+
+    storage
+}
+
+/// Computes the Keyspace id of an ECDSA record created for `pub_key` and `salt`.
+pub fn keyspace_id(vk_hash: &Hash, pub_key: &[u8; 64], salt: Option<&Hash>) -> Hash {
+    keyspace_key_from_storage(vk_hash, &storage(pub_key, salt))
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::rsa_sha256;
+
+/// The header fields of an email covered by a valid DKIM signature.
+#[derive(Debug)]
+pub struct SignedEmail {
+    /// The signing domain (`d=` tag).
+    pub domain: String,
+    /// The lowercased address of the `From` header.
+    pub from: String,
+    /// The `Subject` header value, unfolded.
+    pub subject: String,
+    /// The signature expiration (`x=` tag), if any.
+    pub expiration: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Canonicalization {
+    Simple,
+    Relaxed,
+}
+
+/// A raw header field, without its trailing CRLF.
+struct HeaderField<'a> {
+    name: &'a str,
+    raw: &'a str,
+}
+
+impl HeaderField<'_> {
+    /// Returns the unfolded field value, with its surrounding whitespaces removed.
+    fn value(&self) -> String {
+        let (_, value) = self.raw.split_once(':').unwrap();
+        value.replace("\r\n", "").trim().to_string()
+    }
+
+    /// Canonicalizes the header field as specified in RFC 6376 section 3.4.
+    fn canonicalize(&self, c: Canonicalization) -> String {
+        match c {
+            Canonicalization::Simple => format!("{}\r\n", self.raw),
+            Canonicalization::Relaxed => {
+                let (_, value) = self.raw.split_once(':').unwrap();
+                let value = value
+                    .replace("\r\n", "")
+                    .split([' ', '\t'])
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                format!("{}:{}\r\n", self.name.trim().to_lowercase(), value)
+            }
+        }
+    }
+}
+
+/// Verifies the first DKIM signature (`rsa-sha256`) of the `email` against the RSA key with the
+/// given `modulus` and returns the header fields it covers.
+///
+/// Only the header signature is checked: the body hash (`bh=` tag) is not verified as the body is
+/// not used by the caller.
+pub fn verify_dkim(email: &str, modulus: &[u8]) -> Result<SignedEmail> {
+    // DKIM operates on CRLF line endings.
+    let email = email.replace("\r\n", "\n").replace('\n', "\r\n");
+    let header = match email.split_once("\r\n\r\n") {
+        Some((header, _body)) => header,
+        None => email.trim_end_matches("\r\n"),
+    };
+
+    let fields = parse_header_fields(header)?;
+    let dkim_field = fields
+        .iter()
+        .find(|field| field.name.trim().eq_ignore_ascii_case("dkim-signature"))
+        .context("missing DKIM-Signature header")?;
+    let tags = parse_tags(&dkim_field.value())?;
+
+    let tag = |name: &str| tags.get(name).context(format!("missing DKIM {name}= tag"));
+    ensure!(tag("v")? == "1", "unsupported DKIM version");
+    ensure!(tag("a")? == "rsa-sha256", "unsupported DKIM algorithm");
+    let c = match tags.get("c").map(|c| c.split('/').next().unwrap()) {
+        None | Some("simple") => Canonicalization::Simple,
+        Some("relaxed") => Canonicalization::Relaxed,
+        Some(c) => bail!("unsupported DKIM canonicalization: {c}"),
+    };
+    let signed_names = tag("h")?
+        .split(':')
+        .map(|name| name.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    let domain = tag("d")?.to_lowercase();
+    let signature = STANDARD
+        .decode(tag("b")?)
+        .context("invalid DKIM signature encoding")?;
+    let expiration = tags
+        .get("x")
+        .map(|x| x.parse().context("invalid DKIM x= tag"))
+        .transpose()?;
+
+    // Build the signed data: the signed header fields, selected from the bottom up, followed by the
+    // DKIM-Signature header field without its signature.
+    let mut data = String::new();
+    let mut used = vec![false; fields.len()];
+    let mut signed_fields = HashMap::new();
+    for name in &signed_names {
+        let selected = (0..fields.len()).rev().find(|i| {
+            !used[*i] && fields[*i].name.trim().eq_ignore_ascii_case(name) && !is_dkim(&fields[*i])
+        });
+
+        // Non-existent header fields are signed as the null string.
+        if let Some(i) = selected {
+            used[i] = true;
+            data.push_str(&fields[i].canonicalize(c));
+            signed_fields
+                .entry(name.as_str())
+                .or_insert(fields[i].value());
+        }
+    }
+
+    let unsigned_dkim_field = HeaderField {
+        name: dkim_field.name,
+        raw: &strip_signature(dkim_field.raw),
+    }
+    .canonicalize(c);
+    data.push_str(unsigned_dkim_field.trim_end_matches("\r\n"));
+
+    rsa_sha256::verify(modulus, data.as_bytes(), &signature).context("invalid DKIM signature")?;
+
+    let from = signed_fields
+        .get("from")
+        .context("From header not signed")?;
+    let subject = signed_fields
+        .get("subject")
+        .context("Subject header not signed")?;
+
+    Ok(SignedEmail {
+        domain,
+        from: parse_address(from).to_lowercase(),
+        subject: subject.clone(),
+        expiration,
+    })
+}
+
+fn is_dkim(field: &HeaderField) -> bool {
+    field.name.trim().eq_ignore_ascii_case("dkim-signature")
+}
+
+/// Splits the email header into its (unfolded) fields.
+fn parse_header_fields(header: &str) -> Result<Vec<HeaderField<'_>>> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+
+    for (i, _) in header.match_indices("\r\n") {
+        // A line starting with a whitespace continues the current field.
+        if header[i + 2..].starts_with([' ', '\t']) {
+            continue;
+        }
+
+        fields.push(parse_header_field(&header[start..i])?);
+        start = i + 2;
+    }
+    fields.push(parse_header_field(&header[start..])?);
+
+    Ok(fields)
+}
+
+fn parse_header_field(raw: &str) -> Result<HeaderField<'_>> {
+    let (name, _) = raw.split_once(':').context("invalid header field")?;
+    Ok(HeaderField { name, raw })
+}
+
+/// Parses a DKIM tag list (`tag=value; ...`), removing the whitespaces from the values.
+fn parse_tags(value: &str) -> Result<HashMap<String, String>> {
+    value
+        .split(';')
+        .filter(|tag| !tag.trim().is_empty())
+        .map(|tag| {
+            let (name, value) = tag.split_once('=').context("invalid DKIM tag")?;
+            Ok((
+                name.trim().to_string(),
+                value.split_whitespace().collect::<String>(),
+            ))
+        })
+        .collect()
+}
+
+/// Removes the value of the `b=` tag from a raw DKIM-Signature header field.
+fn strip_signature(raw: &str) -> String {
+    let (name, value) = raw.split_once(':').unwrap();
+    let value = value
+        .split(';')
+        .map(|tag| match tag.split_once('=') {
+            Some((name, _)) if name.trim() == "b" => format!("{name}="),
+            _ => tag.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+
+    format!("{name}:{value}")
+}
+
+/// Extracts the address from a `From` header value (`Name <address>` or `address`).
+fn parse_address(from: &str) -> &str {
+    match (from.rfind('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &from[start + 1..end],
+        _ => from.trim(),
+    }
+}
@@ -0,0 +1,4 @@
+pub mod dkim;
+pub mod inputs;
+pub mod program;
+pub mod storage;
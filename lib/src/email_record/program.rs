@@ -0,0 +1,186 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{keyspace_key_from_storage, time_bounds::TimeBounds, Hash};
+
+use super::{dkim::verify_dkim, inputs::Inputs, storage::Storage};
+
+pub struct Program;
+
+impl Program {
+    /// Runs the email record program and returns the time bounds the record proof must commit to, if any.
+    pub fn run(inputs: &Inputs) -> Option<TimeBounds> {
+        // Ensure the provided storage is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.storage.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "storage does not match with current_key"
+        );
+
+        // Ensure the email is signed by one of the accepted DKIM keys.
+        assert!(
+            inputs
+                .storage
+                .dkim_key_hashes
+                .contains(&Storage::dkim_key_hash(&inputs.modulus)),
+            "unknown DKIM key"
+        );
+        let email = verify_dkim(&inputs.email, &inputs.modulus).expect("invalid DKIM signature");
+
+        // Ensure the email is sent from the record address, and signed by its domain.
+        assert_eq!(
+            Storage::from_hash(&email.from, &inputs.salt),
+            inputs.storage.from_hash,
+            "invalid email sender"
+        );
+        let (_, from_domain) = email.from.rsplit_once('@').expect("invalid email sender");
+        assert_eq!(from_domain, email.domain, "invalid DKIM domain");
+
+        // Ensure the email authorizes this specific update.
+        assert!(
+            email
+                .subject
+                .to_lowercase()
+                .contains(&Self::challenge(&inputs.keyspace_id, &inputs.new_key)),
+            "invalid email subject"
+        );
+
+        // The email can only be used until its DKIM signature expires.
+        email.expiration.map(TimeBounds::until)
+    }
+
+    /// Computes the challenge the email subject must contain: the hex of keccack(keyspace_id, new_key).
+    pub fn challenge(keyspace_id: &Hash, new_key: &Hash) -> String {
+        let mut k = Keccak::v256();
+        let mut challenge = [0; 32];
+        k.update(keyspace_id);
+        k.update(new_key);
+        k.finalize(&mut challenge);
+
+        challenge.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixtures signed with `fixtures/email/dkim_key.pem`, a locally generated RSA key.
+    const MODULUS: &str = include_str!("../../fixtures/email/dkim_modulus.hex");
+    const VALID_EML: &str = include_str!("../../fixtures/email/valid.eml");
+    const SIMPLE_EML: &str = include_str!("../../fixtures/email/simple.eml");
+    const EXPIRING_EML: &str = include_str!("../../fixtures/email/expiring.eml");
+    const UNSIGNED_SUBJECT_EML: &str = include_str!("../../fixtures/email/unsigned_subject.eml");
+    const WRONG_DOMAIN_EML: &str = include_str!("../../fixtures/email/wrong_domain.eml");
+
+    // The `x=` tag of `expiring.eml`.
+    const EXPIRATION: u64 = 1_792_397_546;
+    const VK_HASH: Hash = [0xaa; 32];
+    const SALT: Hash = [7; 32];
+
+    fn modulus() -> Vec<u8> {
+        let modulus = MODULUS.trim();
+        (0..modulus.len() / 2)
+            .map(|i| u8::from_str_radix(&modulus[2 * i..2 * i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Returns the inputs for the fixtures `email`, whose subject authorizes the update to [42; 32].
+    fn inputs(email: &str, new_key: Hash) -> Inputs {
+        let modulus = modulus();
+        let storage = Storage {
+            from_hash: Storage::from_hash("alice@example.com", &SALT),
+            dkim_key_hashes: vec![Storage::dkim_key_hash(&modulus)],
+        };
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &storage.to_bytes());
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            storage,
+            email: email.to_string(),
+            modulus,
+            salt: SALT,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run_relaxed() {
+        let inputs = inputs(VALID_EML, [42; 32]);
+        assert_eq!(Program::run(&inputs), None);
+    }
+
+    #[test]
+    fn test_run_simple() {
+        let inputs = inputs(SIMPLE_EML, [42; 32]);
+        assert_eq!(Program::run(&inputs), None);
+    }
+
+    #[test]
+    fn test_run_lf_line_endings() {
+        let inputs = inputs(&VALID_EML.replace("\r\n", "\n"), [42; 32]);
+        assert_eq!(Program::run(&inputs), None);
+    }
+
+    #[test]
+    fn test_run_expiring() {
+        let inputs = inputs(EXPIRING_EML, [42; 32]);
+        assert_eq!(Program::run(&inputs), Some(TimeBounds::until(EXPIRATION)));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid email subject")]
+    fn test_run_invalid_subject() {
+        let inputs = inputs(VALID_EML, [43; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "storage does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(VALID_EML, [42; 32]);
+        inputs.storage.dkim_key_hashes.clear();
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid email sender")]
+    fn test_run_invalid_salt() {
+        let mut inputs = inputs(VALID_EML, [42; 32]);
+        inputs.salt = [8; 32];
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid DKIM domain")]
+    fn test_run_invalid_domain() {
+        let inputs = inputs(WRONG_DOMAIN_EML, [42; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "Subject header not signed")]
+    fn test_run_unsigned_subject() {
+        let inputs = inputs(UNSIGNED_SUBJECT_EML, [42; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid DKIM signature")]
+    fn test_run_tampered_header() {
+        let inputs = inputs(
+            &VALID_EML.replace("Keyspace recovery", "Keyspace recovery!"),
+            [42; 32],
+        );
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown DKIM key")]
+    fn test_run_unknown_key() {
+        let mut inputs = inputs(VALID_EML, [42; 32]);
+        inputs.modulus[0] ^= 1;
+        Program::run(&inputs);
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::Hash;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Storage {
+    /// The salted email address hash: keccak(from, salt).
+    pub from_hash: Hash,
+    /// The hashes of the DKIM RSA keys accepted to sign the emails: keccak(modulus).
+    pub dkim_key_hashes: Vec<Hash>,
+}
+
+impl Storage {
+    /// Encodes the storage: from_hash || dkim_key_hashes_len || dkim_key_hashes.
+    ///
+    /// Lengths are encoded as big endian u16.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + 2 + self.dkim_key_hashes.len() * 32);
+        bytes.extend_from_slice(&self.from_hash);
+
+        let len: u16 = self
+            .dkim_key_hashes
+            .len()
+            .try_into()
+            .expect("too many keys");
+        bytes.extend_from_slice(&len.to_be_bytes());
+        for dkim_key_hash in &self.dkim_key_hashes {
+            bytes.extend_from_slice(dkim_key_hash);
+        }
+
+        bytes
+    }
+
+    /// Computes the salted email address hash: keccak(from, salt).
+    ///
+    /// The address is lowercased before being hashed.
+    pub fn from_hash(from: &str, salt: &Hash) -> Hash {
+        let mut k = Keccak::v256();
+        let mut from_hash = [0; 32];
+        k.update(from.to_lowercase().as_bytes());
+        k.update(salt);
+        k.finalize(&mut from_hash);
+        from_hash
+    }
+
+    /// Computes the hash of a DKIM RSA key: keccak(modulus).
+    pub fn dkim_key_hash(modulus: &[u8]) -> Hash {
+        let mut k = Keccak::v256();
+        let mut dkim_key_hash = [0; 32];
+        k.update(modulus);
+        k.finalize(&mut dkim_key_hash);
+        dkim_key_hash
+    }
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the secret opening the hash lock.
+    pub secret: Vec<u8>,
+    /// Private input: the optional salt of the hash lock.
+    pub salt: Option<Hash>,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
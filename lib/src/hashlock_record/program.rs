@@ -0,0 +1,92 @@
+use crate::keyspace_key_from_storage;
+
+use super::{inputs::Inputs, storage::lock};
+
+pub struct Program;
+
+impl Program {
+    pub fn run(inputs: &Inputs) {
+        // Ensure the revealed secret opens the lock committed by the `current_key`.
+        let lock = lock(&inputs.secret, inputs.salt.as_ref());
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &lock);
+        assert_eq!(
+            inputs.current_key, current_key,
+            "secret does not open the hash lock of current_key"
+        );
+
+        // The new storage can not reuse the same lock. This only rejects a no-op rotation: the new
+        // storage is not visible to the program, so a rotation to another lock of the same secret
+        // (with another salt or under another vk) keeps the secret usable. The secret is only spent
+        // once the id is rotated to a storage that does not depend on it.
+        assert_ne!(
+            inputs.new_key, current_key,
+            "new storage reuses the hash lock"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Hash;
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+    const SECRET: &[u8] = b"correct horse battery staple";
+    const SALT: Hash = [7; 32];
+
+    fn inputs(salt: Option<Hash>, new_key: Hash) -> Inputs {
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &lock(SECRET, salt.as_ref()));
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            secret: SECRET.to_vec(),
+            salt,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        Program::run(&inputs(None, [42; 32]));
+    }
+
+    #[test]
+    fn test_run_salted() {
+        Program::run(&inputs(Some(SALT), [42; 32]));
+    }
+
+    #[test]
+    fn test_run_rotate_to_new_lock() {
+        let new_key = keyspace_key_from_storage(&VK_HASH, &lock(b"new secret", Some(&SALT)));
+        Program::run(&inputs(Some(SALT), new_key));
+    }
+
+    #[test]
+    #[should_panic(expected = "secret does not open the hash lock of current_key")]
+    fn test_run_invalid_secret() {
+        let mut inputs = inputs(None, [42; 32]);
+        inputs.secret = b"wrong secret".to_vec();
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "secret does not open the hash lock of current_key")]
+    fn test_run_missing_salt() {
+        let mut inputs = inputs(Some(SALT), [42; 32]);
+        inputs.salt = None;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "new storage reuses the hash lock")]
+    fn test_run_reuse_lock() {
+        let inputs = inputs(
+            Some(SALT),
+            keyspace_key_from_storage(&VK_HASH, &lock(SECRET, Some(&SALT))),
+        );
+        Program::run(&inputs);
+    }
+}
@@ -0,0 +1,15 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::Hash;
+
+/// Computes the hash lock, which is the record storage: keccack(secret [, salt]).
+pub fn lock(secret: &[u8], salt: Option<&Hash>) -> Hash {
+    let mut k = Keccak::v256();
+    let mut lock = [0; 32];
+    k.update(secret);
+    if let Some(salt) = salt {
+        k.update(salt);
+    }
+    k.finalize(&mut lock);
+    lock
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, ensure, Result};
+use imt::circuits::mutate::IMTMutate;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::Keccak;
+
+use crate::Hash;
+
+/// A proof that a Keyspace id is (or is not) registered under a Keyspace root.
+///
+/// The IMT only exposes mutations, which are reused as proofs:
+/// - an inclusion proof is an update of the id to its current key, which leaves the root
+///   unchanged and proves the id node (with its key) is in the tree,
+/// - an exclusion proof is an insertion of the id, which proves its low nullifier node (the node
+///   with the greatest lower key) is in the tree and points past the id.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct KeyspaceProof {
+    pub root: Hash,
+    pub keyspace_id: Hash,
+    pub imt_mutate: IMTMutate<Hash, Hash>,
+}
+
+impl KeyspaceProof {
+    /// Kind of the inclusion proofs in their Solidity encoding.
+    pub const INCLUSION: u8 = 0;
+    /// Kind of the exclusion proofs in their Solidity encoding.
+    pub const EXCLUSION: u8 = 1;
+
+    /// Verifies the proof against `root`.
+    ///
+    /// Returns the current key of `keyspace_id` if it is registered, and `None` otherwise.
+    pub fn verify(&self, root: &Hash, keyspace_id: &Hash) -> Result<Option<Hash>> {
+        ensure!(self.root == *root, "proof is for another Keyspace root");
+        ensure!(
+            self.keyspace_id == *keyspace_id,
+            "proof is for another Keyspace id"
+        );
+
+        let key = match &self.imt_mutate {
+            IMTMutate::Update(update) => {
+                ensure!(
+                    update.node.key == *keyspace_id,
+                    "invalid inclusion proof node"
+                );
+                ensure!(
+                    update.new_value == update.node.value,
+                    "inclusion proof updates the key"
+                );
+                Some(update.node.value)
+            }
+            IMTMutate::Insert(insert) => {
+                ensure!(
+                    insert.node.key == *keyspace_id,
+                    "invalid exclusion proof node"
+                );
+                None
+            }
+        };
+
+        // Verifying the mutation checks the proven node against the old root and, for
+        // insertions, that the low nullifier node brackets the id.
+        let new_root = self.imt_mutate.verify(Keccak::v256, *root)?;
+        if key.is_some() {
+            ensure!(new_root == *root, "inclusion proof updates the key");
+        }
+
+        Ok(key)
+    }
+
+    /// Verifies the proof is an inclusion proof against `root`, and returns the current key of
+    /// `keyspace_id`.
+    pub fn verify_inclusion(&self, root: &Hash, keyspace_id: &Hash) -> Result<Hash> {
+        let key = self.verify(root, keyspace_id)?;
+        key.ok_or_else(|| anyhow!("Keyspace id is not registered"))
+    }
+
+    /// Verifies the proof is an exclusion proof against `root`.
+    pub fn verify_exclusion(&self, root: &Hash, keyspace_id: &Hash) -> Result<()> {
+        let key = self.verify(root, keyspace_id)?;
+        ensure!(key.is_none(), "Keyspace id is registered");
+        Ok(())
+    }
+
+    /// ABI encodes the proof, to be decoded by Solidity verifiers with:
+    /// ```solidity
+    /// (
+    ///     uint8 kind,
+    ///     bytes32 root,
+    ///     bytes32 keyspaceId,
+    ///     uint64 treeSize,
+    ///     uint64 nodeIndex,
+    ///     bytes32 nodeKey,
+    ///     bytes32 nodeValue,
+    ///     bytes32 nodeNextKey,
+    ///     uint256 siblingsMask,
+    ///     bytes32[] siblings
+    /// ) = abi.decode(proof, (uint8, bytes32, bytes32, uint64, uint64, bytes32, bytes32, bytes32, uint256, bytes32[]));
+    /// ```
+    ///
+    /// The node is the id node for inclusion proofs ([KeyspaceProof::INCLUSION]), and its low
+    /// nullifier node for exclusion proofs ([KeyspaceProof::EXCLUSION]). Bit `i` of the siblings
+    /// mask is set if the sibling at level `i` is not empty, in which case it is the next element
+    /// of the siblings array.
+    pub fn to_abi_bytes(&self) -> Vec<u8> {
+        let (kind, size, node, siblings) = match &self.imt_mutate {
+            IMTMutate::Update(update) => (
+                Self::INCLUSION,
+                update.size,
+                &update.node,
+                &update.node_siblings,
+            ),
+            IMTMutate::Insert(insert) => (
+                Self::EXCLUSION,
+                insert.old_size,
+                &insert.ln_node,
+                &insert.ln_siblings,
+            ),
+        };
+
+        let mut siblings_mask = [0; 32];
+        let mut present_siblings = vec![];
+        for (level, sibling) in siblings.iter().enumerate() {
+            if let Some(sibling) = sibling {
+                siblings_mask[31 - level / 8] |= 1 << (level % 8);
+                present_siblings.push(*sibling);
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(32 * (11 + present_siblings.len()));
+        bytes.extend(abi_uint(kind as u64));
+        bytes.extend(self.root);
+        bytes.extend(self.keyspace_id);
+        bytes.extend(abi_uint(size));
+        bytes.extend(abi_uint(node.index));
+        bytes.extend(node.key);
+        bytes.extend(node.value);
+        bytes.extend(node.next_key);
+        bytes.extend(siblings_mask);
+
+        // The siblings array is the only dynamic member: its offset follows the 10 head words.
+        bytes.extend(abi_uint(10 * 32));
+        bytes.extend(abi_uint(present_siblings.len() as u64));
+        for sibling in present_siblings {
+            bytes.extend(sibling);
+        }
+
+        bytes
+    }
+}
+
+fn abi_uint(value: u64) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+#[cfg(test)]
+mod tests {
+    use imt::circuits::imt::Imt;
+
+    use super::*;
+
+    fn tree() -> Imt<Hash, Hash, Keccak> {
+        let mut tree = Imt::new(Keccak::v256);
+        tree.insert_node([1; 32], [10; 32]);
+        tree.insert_node([3; 32], [30; 32]);
+        tree
+    }
+
+    fn inclusion_proof() -> KeyspaceProof {
+        let mut tree = tree();
+        let root = tree.root;
+        KeyspaceProof {
+            root,
+            keyspace_id: [3; 32],
+            imt_mutate: tree.update_node([3; 32], [30; 32]),
+        }
+    }
+
+    fn exclusion_proof() -> KeyspaceProof {
+        let mut tree = tree();
+        let root = tree.root;
+        KeyspaceProof {
+            root,
+            keyspace_id: [2; 32],
+            imt_mutate: tree.insert_node([2; 32], [0; 32]),
+        }
+    }
+
+    #[test]
+    fn test_inclusion() {
+        let proof = inclusion_proof();
+
+        assert_eq!(
+            proof.verify_inclusion(&tree().root, &[3; 32]).unwrap(),
+            [30; 32]
+        );
+        assert!(proof.verify_exclusion(&tree().root, &[3; 32]).is_err());
+    }
+
+    #[test]
+    fn test_exclusion() {
+        let proof = exclusion_proof();
+
+        proof.verify_exclusion(&tree().root, &[2; 32]).unwrap();
+        assert!(proof.verify_inclusion(&tree().root, &[2; 32]).is_err());
+    }
+
+    #[test]
+    fn test_other_id_or_root() {
+        let proof = inclusion_proof();
+
+        assert!(proof.verify(&tree().root, &[1; 32]).is_err());
+        assert!(proof.verify(&[0xff; 32], &[3; 32]).is_err());
+    }
+
+    #[test]
+    fn test_inclusion_proof_updating_the_key() {
+        let mut tree = tree();
+        let root = tree.root;
+        let proof = KeyspaceProof {
+            root,
+            keyspace_id: [3; 32],
+            imt_mutate: tree.update_node([3; 32], [31; 32]),
+        };
+
+        let err = proof.verify(&root, &[3; 32]).unwrap_err();
+        assert_eq!(err.to_string(), "inclusion proof updates the key");
+    }
+
+    #[test]
+    fn test_abi_encoding() {
+        let proof = inclusion_proof();
+        let bytes = proof.to_abi_bytes();
+
+        let IMTMutate::Update(update) = &proof.imt_mutate else {
+            unreachable!()
+        };
+        let siblings = update.node_siblings.iter().flatten().count();
+
+        assert_eq!(bytes.len(), 32 * (11 + siblings));
+        assert_eq!(bytes[31], KeyspaceProof::INCLUSION);
+        assert_eq!(&bytes[32..64], &proof.root);
+        assert_eq!(&bytes[64..96], &[3; 32]);
+        assert_eq!(&bytes[160..192], &[3; 32]);
+        assert_eq!(&bytes[192..224], &[30; 32]);
+        assert_eq!(&bytes[9 * 32..10 * 32], &abi_uint(320));
+        assert_eq!(&bytes[10 * 32..11 * 32], &abi_uint(siblings as u64));
+
+        assert_eq!(
+            exclusion_proof().to_abi_bytes()[31],
+            KeyspaceProof::EXCLUSION
+        );
+    }
+}
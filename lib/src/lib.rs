@@ -1,7 +1,27 @@
+use anyhow::{bail, Result};
 use tiny_keccak::{Hasher, Keccak};
 
+use time_bounds::TimeBounds;
+
+pub mod allowlist_record;
 pub mod batcher;
+pub mod bip137_record;
+pub mod delegate_record;
 pub mod ecdsa_record;
+pub mod email_record;
+pub mod hashlock_record;
+pub mod keyspace_proof;
+pub mod merkle;
+pub mod oidc_record;
+pub mod policy_record;
+pub mod recovery_record;
+pub mod rsa_sha256;
+pub mod schnorr_record;
+pub mod session_record;
+#[cfg(test)]
+mod test_utils;
+pub mod time_bounds;
+pub mod wots_record;
 
 pub type Hash = [u8; 32];
 
@@ -14,6 +34,12 @@ pub fn hash_storage(storage: &[u8]) -> Hash {
     storage_hash
 }
 
+/// Computes the Keyspace key of a record: keccack(keccack(storage), vk_hash).
+///
+/// As the key commits to the record program `vk_hash`, migrating a Keyspace id to another record
+/// program is a regular update: the current record approves a `new_key` computed with the new
+/// program `vk_hash` and storage. The following updates are then proven with the new program, the
+/// batcher checking the `vk_hash` of each record proof against the id current key.
 pub fn keyspace_key_from_storage(vk_hash: &Hash, storage: &[u8]) -> Hash {
     keyspace_key_from_storage_hash(vk_hash, &hash_storage(storage))
 }
@@ -28,3 +54,85 @@ pub fn keyspace_key_from_storage_hash(vk_hash: &Hash, storage_hash: &Hash) -> Ha
 
     key
 }
+
+/// Returns the public values committed by record programs: keyspace_id || current_key || new_key,
+/// optionally followed by the record time bounds and by the Keyspace root the record proof reads from.
+pub fn record_public_values(
+    keyspace_id: &Hash,
+    current_key: &Hash,
+    new_key: &Hash,
+    time_bounds: Option<&TimeBounds>,
+    keyspace_root: Option<&Hash>,
+) -> Vec<u8> {
+    let mut public_values = Vec::with_capacity(144);
+    public_values.extend_from_slice(keyspace_id);
+    public_values.extend_from_slice(current_key);
+    public_values.extend_from_slice(new_key);
+    if let Some(time_bounds) = time_bounds {
+        public_values.extend_from_slice(&time_bounds.to_bytes());
+    }
+    if let Some(keyspace_root) = keyspace_root {
+        public_values.extend_from_slice(keyspace_root);
+    }
+
+    public_values
+}
+
+/// The purpose word closing the public values of a delegation approval (see [approval_public_values]).
+pub const DELEGATION_APPROVAL: Hash = *b"KEYSPACE_DELEGATION_APPROVAL\0\0\0\0";
+
+/// Returns the public values committed by a record program approving a delegated update (see
+/// [delegate_record]): the [record_public_values] of the parent, closed by [DELEGATION_APPROVAL].
+///
+/// The purpose word sits where a Keyspace root would be, so that an approval is never an update:
+/// it is rejected by [parse_record_public_values] and its Keyspace root is never accepted by the batcher.
+pub fn approval_public_values(
+    keyspace_id: &Hash,
+    current_key: &Hash,
+    new_key: &Hash,
+    time_bounds: Option<&TimeBounds>,
+) -> Vec<u8> {
+    record_public_values(
+        keyspace_id,
+        current_key,
+        new_key,
+        time_bounds,
+        Some(&DELEGATION_APPROVAL),
+    )
+}
+
+/// The public values committed by record programs (see [record_public_values]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordPublicValues {
+    pub keyspace_id: Hash,
+    pub current_key: Hash,
+    pub new_key: Hash,
+    pub time_bounds: Option<TimeBounds>,
+    pub keyspace_root: Option<Hash>,
+}
+
+/// Parses the public values committed by record programs (see [record_public_values]).
+pub fn parse_record_public_values(public_values: &[u8]) -> Result<RecordPublicValues> {
+    let (time_bounds, keyspace_root) = match public_values.len() {
+        96 => (None, None),
+        112 => (Some(&public_values[96..112]), None),
+        128 => (None, Some(&public_values[96..128])),
+        144 => (
+            Some(&public_values[96..112]),
+            Some(&public_values[112..144]),
+        ),
+        len => bail!("invalid record public values length: {len}"),
+    };
+    if keyspace_root == Some(&DELEGATION_APPROVAL[..]) {
+        bail!("record public values are a delegation approval, not an update");
+    }
+
+    Ok(RecordPublicValues {
+        keyspace_id: public_values[..32].try_into().unwrap(),
+        current_key: public_values[32..64].try_into().unwrap(),
+        new_key: public_values[64..96].try_into().unwrap(),
+        time_bounds: time_bounds
+            .map(|time_bounds| TimeBounds::from_bytes(time_bounds.try_into().unwrap())),
+        keyspace_root: keyspace_root.map(|keyspace_root| keyspace_root.try_into().unwrap()),
+    })
+}
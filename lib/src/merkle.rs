@@ -0,0 +1,53 @@
+//! Keccak Merkle trees, shared by the allowlist and WOTS records: changing the hashing changes the
+//! roots committed by both.
+
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::Hash;
+
+/// The domain separation prefix of the leaves.
+const LEAF_PREFIX: u8 = 0x00;
+/// The domain separation prefix of the internal nodes, so that a node can not be presented as a
+/// leaf (or the other way around).
+const NODE_PREFIX: u8 = 0x01;
+
+/// Computes the leaf of `data`: keccack(0x00, data).
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    let mut k = Keccak::v256();
+    let mut leaf = [0; 32];
+    k.update(&[LEAF_PREFIX]);
+    k.update(data);
+    k.finalize(&mut leaf);
+    leaf
+}
+
+/// Computes a Merkle node: keccack(0x01, left, right).
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut k = Keccak::v256();
+    let mut node = [0; 32];
+    k.update(&[NODE_PREFIX]);
+    k.update(left);
+    k.update(right);
+    k.finalize(&mut node);
+    node
+}
+
+/// Computes the Merkle root from a `leaf` at `index` and its `path` (the sibling nodes, from the
+/// leaf level up to the root).
+///
+/// Returns `None` if `index` does not fit in a tree of the path depth.
+pub fn compute_root(leaf: &Hash, index: u32, path: &[Hash]) -> Option<Hash> {
+    if path.len() > 32 || u64::from(index) >> path.len() != 0 {
+        return None;
+    }
+
+    let root = path
+        .iter()
+        .enumerate()
+        .fold(*leaf, |node, (level, sibling)| match (index >> level) & 1 {
+            0 => node_hash(&node, sibling),
+            _ => node_hash(sibling, &node),
+        });
+
+    Some(root)
+}
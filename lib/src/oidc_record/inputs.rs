@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+use super::storage::Storage;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the record storage committed by `current_key`.
+    pub storage: Storage,
+    /// Private input: the RS256 JWT (ID token) whose nonce is keccak(keyspace_id, current_key, new_key).
+    pub jwt: String,
+    /// Private input: the modulus (big endian) of the issuer RSA key that signed the JWT.
+    pub modulus: Vec<u8>,
+    /// Private input: the salt used to hash the JWT subject.
+    pub salt: Hash,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
@@ -0,0 +1,55 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+
+use crate::rsa_sha256;
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    alg: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Audience {
+    pub fn contains(&self, aud: &str) -> bool {
+        match self {
+            Audience::One(one) => one == aud,
+            Audience::Many(many) => many.iter().any(|one| one == aud),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Claims {
+    pub iss: String,
+    pub aud: Audience,
+    pub sub: String,
+    pub nonce: String,
+    pub exp: u64,
+}
+
+/// Verifies the RS256 signature of the `jwt` against the RSA key with the given `modulus` and
+/// returns its claims.
+///
+/// Only the signature is checked here: validating the claims is left to the caller.
+pub fn verify_rs256(jwt: &str, modulus: &[u8]) -> Result<Claims> {
+    let (signed, signature) = jwt.rsplit_once('.').context("invalid JWT format")?;
+    let (header, payload) = signed.split_once('.').context("invalid JWT format")?;
+
+    let header: Header =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?).context("invalid JWT header")?;
+    if header.alg != "RS256" {
+        bail!("unsupported JWT alg: {}", header.alg);
+    }
+
+    let signature = URL_SAFE_NO_PAD.decode(signature)?;
+    rsa_sha256::verify(modulus, signed.as_bytes(), &signature).context("invalid JWT signature")?;
+
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?).context("invalid JWT claims")
+}
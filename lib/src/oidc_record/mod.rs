@@ -0,0 +1,4 @@
+pub mod inputs;
+pub mod jwt;
+pub mod program;
+pub mod storage;
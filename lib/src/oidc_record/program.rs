@@ -0,0 +1,184 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{keyspace_key_from_storage, time_bounds::TimeBounds, Hash};
+
+use super::{inputs::Inputs, jwt::verify_rs256, storage::Storage};
+
+pub struct Program;
+
+impl Program {
+    /// Runs the OIDC record program and returns the time bounds the record proof must commit to.
+    pub fn run(inputs: &Inputs) -> TimeBounds {
+        // Ensure the provided storage is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.storage.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "storage does not match with current_key"
+        );
+
+        // Ensure the JWT is signed by one of the accepted issuer keys.
+        assert!(
+            inputs
+                .storage
+                .jwk_hashes
+                .contains(&Storage::jwk_hash(&inputs.modulus)),
+            "unknown issuer key"
+        );
+        let claims = verify_rs256(&inputs.jwt, &inputs.modulus).expect("invalid JWT");
+
+        // Ensure the JWT is issued for the record identity.
+        assert_eq!(claims.iss, inputs.storage.iss, "invalid JWT issuer");
+        assert!(
+            claims.aud.contains(&inputs.storage.aud),
+            "invalid JWT audience"
+        );
+        assert_eq!(
+            Storage::sub_hash(&claims.sub, &inputs.salt),
+            inputs.storage.sub_hash,
+            "invalid JWT subject"
+        );
+
+        // Ensure the JWT authorizes this specific update.
+        assert_eq!(
+            claims.nonce,
+            Self::nonce(&inputs.keyspace_id, &inputs.current_key, &inputs.new_key),
+            "invalid JWT nonce"
+        );
+
+        // The JWT can only be used before it expires (RFC 7519: the current time MUST be before
+        // `exp`).
+        let valid_until = claims.exp.checked_sub(1).expect("JWT expired");
+        TimeBounds::until(valid_until)
+    }
+
+    /// Computes the JWT nonce authorizing an update: the 0x-prefixed hex of keccack(keyspace_id, current_key, new_key).
+    pub fn nonce(keyspace_id: &Hash, current_key: &Hash, new_key: &Hash) -> String {
+        let mut k = Keccak::v256();
+        let mut nonce = [0; 32];
+        k.update(keyspace_id);
+        k.update(current_key);
+        k.update(new_key);
+        k.finalize(&mut nonce);
+
+        nonce
+            .iter()
+            .fold(String::from("0x"), |hex, byte| hex + &format!("{byte:02x}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixtures signed with `fixtures/oidc/rsa_key.pem`, a locally generated RSA key.
+    const MODULUS: &str = include_str!("../../fixtures/oidc/rsa_modulus.hex");
+    const VALID_JWT: &str = include_str!("../../fixtures/oidc/valid.jwt");
+    const AUDIENCE_LIST_JWT: &str = include_str!("../../fixtures/oidc/audience_list.jwt");
+    const WRONG_ISSUER_JWT: &str = include_str!("../../fixtures/oidc/wrong_issuer.jwt");
+    const HS256_JWT: &str = include_str!("../../fixtures/oidc/hs256.jwt");
+
+    // The `exp` claim of the fixtures.
+    const EXP: u64 = 2_000_000_000;
+    const VK_HASH: Hash = [0xaa; 32];
+    const SALT: Hash = [7; 32];
+
+    fn modulus() -> Vec<u8> {
+        let modulus = MODULUS.trim();
+        (0..modulus.len() / 2)
+            .map(|i| u8::from_str_radix(&modulus[2 * i..2 * i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// Returns the inputs for the fixtures `jwt`, whose nonce authorizes the update to [42; 32].
+    fn inputs(jwt: &str, new_key: Hash) -> Inputs {
+        let modulus = modulus();
+        let storage = Storage {
+            iss: "https://accounts.example.com".to_string(),
+            aud: "keyspace-client-id".to_string(),
+            sub_hash: Storage::sub_hash("1234567890", &SALT),
+            jwk_hashes: vec![Storage::jwk_hash(&modulus)],
+        };
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &storage.to_bytes());
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            storage,
+            jwt: jwt.trim().to_string(),
+            modulus,
+            salt: SALT,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let inputs = inputs(VALID_JWT, [42; 32]);
+        assert_eq!(Program::run(&inputs), TimeBounds::until(EXP - 1));
+    }
+
+    #[test]
+    fn test_run_audience_list() {
+        let inputs = inputs(AUDIENCE_LIST_JWT, [42; 32]);
+        assert_eq!(Program::run(&inputs), TimeBounds::until(EXP - 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(VALID_JWT, [42; 32]);
+        inputs.storage.jwk_hashes.clear();
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JWT nonce")]
+    fn test_run_invalid_nonce() {
+        let inputs = inputs(VALID_JWT, [43; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JWT issuer")]
+    fn test_run_invalid_issuer() {
+        let inputs = inputs(WRONG_ISSUER_JWT, [42; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JWT subject")]
+    fn test_run_invalid_salt() {
+        let mut inputs = inputs(VALID_JWT, [42; 32]);
+        inputs.salt = [8; 32];
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JWT")]
+    fn test_run_invalid_alg() {
+        let inputs = inputs(HS256_JWT, [42; 32]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid JWT")]
+    fn test_run_invalid_signature() {
+        let mut inputs = inputs(VALID_JWT, [42; 32]);
+
+        // Swap the signature for the one of another token.
+        let (signed, _) = inputs.jwt.rsplit_once('.').unwrap();
+        let (_, signature) = WRONG_ISSUER_JWT.trim().rsplit_once('.').unwrap();
+        inputs.jwt = format!("{signed}.{signature}");
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown issuer key")]
+    fn test_run_unknown_key() {
+        let mut inputs = inputs(VALID_JWT, [42; 32]);
+        inputs.modulus[0] ^= 1;
+        Program::run(&inputs);
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::Hash;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Storage {
+    /// The OIDC issuer (`iss` claim).
+    pub iss: String,
+    /// The OIDC audience, i.e. the app client id (`aud` claim).
+    pub aud: String,
+    /// The salted subject hash: keccak(sub, salt).
+    pub sub_hash: Hash,
+    /// The hashes of the issuer RSA keys accepted to sign the JWTs: keccak(modulus).
+    pub jwk_hashes: Vec<Hash>,
+}
+
+impl Storage {
+    /// Encodes the storage: iss_len || iss || aud_len || aud || sub_hash || jwk_hashes_len || jwk_hashes.
+    ///
+    /// Lengths are encoded as big endian u16.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for field in [self.iss.as_bytes(), self.aud.as_bytes()] {
+            let len: u16 = field.len().try_into().expect("field too long");
+            bytes.extend_from_slice(&len.to_be_bytes());
+            bytes.extend_from_slice(field);
+        }
+
+        bytes.extend_from_slice(&self.sub_hash);
+
+        let len: u16 = self.jwk_hashes.len().try_into().expect("too many keys");
+        bytes.extend_from_slice(&len.to_be_bytes());
+        for jwk_hash in &self.jwk_hashes {
+            bytes.extend_from_slice(jwk_hash);
+        }
+
+        bytes
+    }
+
+    /// Computes the salted subject hash: keccak(sub, salt).
+    pub fn sub_hash(sub: &str, salt: &Hash) -> Hash {
+        let mut k = Keccak::v256();
+        let mut sub_hash = [0; 32];
+        k.update(sub.as_bytes());
+        k.update(salt);
+        k.finalize(&mut sub_hash);
+        sub_hash
+    }
+
+    /// Computes the hash of an RSA key: keccak(modulus).
+    pub fn jwk_hash(modulus: &[u8]) -> Hash {
+        let mut k = Keccak::v256();
+        let mut jwk_hash = [0; 32];
+        k.update(modulus);
+        k.finalize(&mut jwk_hash);
+        jwk_hash
+    }
+}
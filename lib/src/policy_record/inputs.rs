@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+use super::policy::{Policy, Witness};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the policy tree, which is the record storage.
+    pub policy: Policy,
+    /// Private input: the witness satisfying the `policy` for keccack(keyspace_id, new_key).
+    pub witness: Witness,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
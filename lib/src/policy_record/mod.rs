@@ -0,0 +1,3 @@
+pub mod inputs;
+pub mod policy;
+pub mod program;
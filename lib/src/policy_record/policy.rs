@@ -0,0 +1,315 @@
+use anyhow::{bail, ensure, Context, Result};
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    ecdsa_record::k_signature::{Address, KSignature},
+    Hash,
+};
+
+/// A policy tree: the conditions to satisfy to update a policy record.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Policy {
+    /// A secp256k1 signature recovering to the given Ethereum address.
+    Secp256k1 { signer: Address },
+    /// A P-256 signature from the given SEC1 encoded public key.
+    P256 { pub_key: Vec<u8> },
+    /// An Ed25519 signature from the given public key.
+    Ed25519 { pub_key: Hash },
+    /// The reveal of a preimage of the given keccack hash.
+    Preimage { hash: Hash },
+    /// All the sub-policies must be satisfied.
+    And(Vec<Policy>),
+    /// At least one of the sub-policies must be satisfied.
+    Or(Vec<Policy>),
+    /// At least `k` of the sub-policies must be satisfied.
+    Threshold { k: u8, policies: Vec<Policy> },
+}
+
+/// A witness satisfying a [Policy]: it has the same shape as the policy tree it satisfies.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Witness {
+    /// The signature of a [Policy::Secp256k1] leaf.
+    Secp256k1(KSignature),
+    /// The 64 bytes `r || s` signature of a [Policy::P256] leaf.
+    P256(Vec<u8>),
+    /// The 64 bytes signature of a [Policy::Ed25519] leaf.
+    Ed25519(Vec<u8>),
+    /// The preimage of a [Policy::Preimage] leaf.
+    Preimage(Vec<u8>),
+    /// The witnesses of an inner node sub-policies, `None` for the ones that are not satisfied.
+    Node(Vec<Option<Witness>>),
+}
+
+impl Policy {
+    const SECP256K1_TAG: u8 = 0;
+    const P256_TAG: u8 = 1;
+    const ED25519_TAG: u8 = 2;
+    const PREIMAGE_TAG: u8 = 3;
+    const AND_TAG: u8 = 4;
+    const OR_TAG: u8 = 5;
+    const THRESHOLD_TAG: u8 = 6;
+
+    /// Serializes the policy tree: each node is encoded as its tag followed by its parameters, inner
+    /// nodes listing their (u8 length prefixed) sub-policies.
+    ///
+    /// Panics if a list does not fit its u8 length prefix: a wrapped length would make two different
+    /// trees encode to the same bytes, and thus to the same storage.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.encode(&mut bytes);
+        bytes
+    }
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Policy::Secp256k1 { signer } => {
+                bytes.push(Self::SECP256K1_TAG);
+                bytes.extend_from_slice(signer);
+            }
+            Policy::P256 { pub_key } => {
+                bytes.push(Self::P256_TAG);
+                bytes.push(u8::try_from(pub_key.len()).expect("P-256 public key too long"));
+                bytes.extend_from_slice(pub_key);
+            }
+            Policy::Ed25519 { pub_key } => {
+                bytes.push(Self::ED25519_TAG);
+                bytes.extend_from_slice(pub_key);
+            }
+            Policy::Preimage { hash } => {
+                bytes.push(Self::PREIMAGE_TAG);
+                bytes.extend_from_slice(hash);
+            }
+            Policy::And(policies) => {
+                bytes.push(Self::AND_TAG);
+                Self::encode_policies(policies, bytes);
+            }
+            Policy::Or(policies) => {
+                bytes.push(Self::OR_TAG);
+                Self::encode_policies(policies, bytes);
+            }
+            Policy::Threshold { k, policies } => {
+                bytes.push(Self::THRESHOLD_TAG);
+                bytes.push(*k);
+                Self::encode_policies(policies, bytes);
+            }
+        }
+    }
+
+    fn encode_policies(policies: &[Policy], bytes: &mut Vec<u8>) {
+        bytes.push(u8::try_from(policies.len()).expect("too many sub-policies"));
+        for policy in policies {
+            policy.encode(bytes);
+        }
+    }
+
+    /// Evaluates the policy against the `witness` for the given `msg_hash`.
+    ///
+    /// Every provided witness must be valid, even in branches that are not needed to satisfy the
+    /// policy. This does not depend on the zkVM and can be used from host code to dry run an update.
+    pub fn evaluate(&self, msg_hash: &Hash, witness: &Witness) -> Result<()> {
+        match (self, witness) {
+            (Policy::Secp256k1 { signer }, Witness::Secp256k1(sig)) => {
+                let recovered = sig.ecrecover_address(msg_hash)?;
+                ensure!(&recovered == signer, "invalid secp256k1 signer");
+            }
+            (Policy::P256 { pub_key }, Witness::P256(sig)) => {
+                let pub_key = p256::ecdsa::VerifyingKey::from_sec1_bytes(pub_key)
+                    .context("invalid P-256 public key")?;
+                let sig =
+                    p256::ecdsa::Signature::from_slice(sig).context("invalid P-256 signature")?;
+                pub_key
+                    .verify_prehash(msg_hash, &sig)
+                    .context("invalid P-256 signature")?;
+            }
+            (Policy::Ed25519 { pub_key }, Witness::Ed25519(sig)) => {
+                let pub_key = ed25519_dalek::VerifyingKey::from_bytes(pub_key)
+                    .context("invalid Ed25519 public key")?;
+                let sig = ed25519_dalek::Signature::from_slice(sig)
+                    .context("invalid Ed25519 signature")?;
+                pub_key
+                    .verify_strict(msg_hash, &sig)
+                    .context("invalid Ed25519 signature")?;
+            }
+            (Policy::Preimage { hash }, Witness::Preimage(preimage)) => {
+                let mut k = Keccak::v256();
+                let mut preimage_hash = [0; 32];
+                k.update(preimage);
+                k.finalize(&mut preimage_hash);
+                ensure!(&preimage_hash == hash, "invalid preimage");
+            }
+            (Policy::And(policies), Witness::Node(witnesses)) => {
+                let satisfied = Self::evaluate_policies(policies, msg_hash, witnesses)?;
+                ensure!(satisfied == policies.len(), "AND policy not satisfied");
+            }
+            (Policy::Or(policies), Witness::Node(witnesses)) => {
+                let satisfied = Self::evaluate_policies(policies, msg_hash, witnesses)?;
+                ensure!(satisfied > 0, "OR policy not satisfied");
+            }
+            (Policy::Threshold { k, policies }, Witness::Node(witnesses)) => {
+                ensure!(
+                    *k > 0 && *k as usize <= policies.len(),
+                    "invalid threshold policy"
+                );
+
+                let satisfied = Self::evaluate_policies(policies, msg_hash, witnesses)?;
+                ensure!(
+                    satisfied >= *k as usize,
+                    "threshold policy not satisfied: {satisfied} of {k}"
+                );
+            }
+            _ => bail!("witness does not match the policy"),
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the provided `witnesses` of an inner node and returns the number of satisfied sub-policies.
+    fn evaluate_policies(
+        policies: &[Policy],
+        msg_hash: &Hash,
+        witnesses: &[Option<Witness>],
+    ) -> Result<usize> {
+        ensure!(!policies.is_empty(), "empty policy node");
+        ensure!(
+            policies.len() == witnesses.len(),
+            "witness does not match the policy"
+        );
+
+        let mut satisfied = 0;
+        for (policy, witness) in policies.iter().zip(witnesses) {
+            if let Some(witness) = witness {
+                policy.evaluate(msg_hash, witness)?;
+                satisfied += 1;
+            }
+        }
+
+        Ok(satisfied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes a policy tree serialized by [Policy::to_bytes], and returns it with the remaining bytes.
+    ///
+    /// The encoding is unambiguous if every tree decodes back from its bytes.
+    fn decode(bytes: &[u8]) -> (Policy, &[u8]) {
+        let (tag, bytes) = bytes.split_first().unwrap();
+        match *tag {
+            Policy::SECP256K1_TAG => (
+                Policy::Secp256k1 {
+                    signer: bytes[..20].try_into().unwrap(),
+                },
+                &bytes[20..],
+            ),
+            Policy::P256_TAG => {
+                let len = bytes[0] as usize;
+                (
+                    Policy::P256 {
+                        pub_key: bytes[1..1 + len].to_vec(),
+                    },
+                    &bytes[1 + len..],
+                )
+            }
+            Policy::ED25519_TAG => (
+                Policy::Ed25519 {
+                    pub_key: bytes[..32].try_into().unwrap(),
+                },
+                &bytes[32..],
+            ),
+            Policy::PREIMAGE_TAG => (
+                Policy::Preimage {
+                    hash: bytes[..32].try_into().unwrap(),
+                },
+                &bytes[32..],
+            ),
+            Policy::AND_TAG => {
+                let (policies, bytes) = decode_policies(bytes);
+                (Policy::And(policies), bytes)
+            }
+            Policy::OR_TAG => {
+                let (policies, bytes) = decode_policies(bytes);
+                (Policy::Or(policies), bytes)
+            }
+            Policy::THRESHOLD_TAG => {
+                let (policies, rest) = decode_policies(&bytes[1..]);
+                (
+                    Policy::Threshold {
+                        k: bytes[0],
+                        policies,
+                    },
+                    rest,
+                )
+            }
+            tag => panic!("unknown tag {tag}"),
+        }
+    }
+
+    fn decode_policies(bytes: &[u8]) -> (Vec<Policy>, &[u8]) {
+        let (len, mut bytes) = bytes.split_first().unwrap();
+        let mut policies = vec![];
+        for _ in 0..*len {
+            let (policy, rest) = decode(bytes);
+            policies.push(policy);
+            bytes = rest;
+        }
+        (policies, bytes)
+    }
+
+    fn key(i: usize) -> Policy {
+        Policy::Secp256k1 {
+            signer: [i as u8; 20],
+        }
+    }
+
+    #[test]
+    fn test_distinct_trees_encode_differently() {
+        // The shapes of the wrapped length collision, within the u8 length prefix.
+        let flat = Policy::And(vec![Policy::Or((1..=255).map(key).collect())]);
+        let nested = Policy::And(
+            [Policy::Or(vec![key(1)])]
+                .into_iter()
+                .chain((2..=255).map(key))
+                .collect(),
+        );
+        let trees = [
+            flat,
+            nested,
+            Policy::Threshold {
+                k: 2,
+                policies: vec![
+                    key(1),
+                    Policy::P256 {
+                        pub_key: vec![2; 33],
+                    },
+                    Policy::Ed25519 { pub_key: [3; 32] },
+                    Policy::Preimage { hash: [4; 32] },
+                ],
+            },
+        ];
+
+        for tree in &trees {
+            let bytes = tree.to_bytes();
+            assert_eq!(decode(&bytes), (tree.clone(), [].as_slice()));
+        }
+        assert_ne!(trees[0].to_bytes(), trees[1].to_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "too many sub-policies")]
+    fn test_too_many_sub_policies() {
+        Policy::And(vec![Policy::Or((1..=257).map(key).collect())]).to_bytes();
+    }
+
+    #[test]
+    #[should_panic(expected = "P-256 public key too long")]
+    fn test_p256_public_key_too_long() {
+        Policy::P256 {
+            pub_key: vec![2; 256],
+        }
+        .to_bytes();
+    }
+}
@@ -0,0 +1,275 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{keyspace_key_from_storage, Hash};
+
+use super::inputs::Inputs;
+
+pub struct Program;
+
+impl Program {
+    pub fn run(inputs: &Inputs) {
+        // Ensure the provided policy is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.policy.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "policy does not match with current_key"
+        );
+
+        // Ensure the witness satisfies the policy for this update.
+        let msg_hash = Self::msg_hash(&inputs.keyspace_id, &inputs.new_key);
+        if let Err(err) = inputs.policy.evaluate(&msg_hash, &inputs.witness) {
+            panic!("policy not satisfied: {err:#}");
+        }
+    }
+
+    /// Computes the `msg_hash` to sign: keccack(keyspace_id, new_key).
+    pub fn msg_hash(keyspace_id: &Hash, new_key: &Hash) -> Hash {
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(keyspace_id);
+        k.update(new_key);
+        k.finalize(&mut msg_hash);
+        msg_hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::Signer;
+    use p256::ecdsa::signature::hazmat::PrehashSigner;
+
+    use crate::{
+        ecdsa_record::k_signature::KSignature,
+        policy_record::policy::{Policy, Witness},
+        test_utils::{address, signing_key},
+    };
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+    const PREIMAGE: &[u8] = b"guardian secret";
+
+    fn p256_key() -> p256::ecdsa::SigningKey {
+        p256::ecdsa::SigningKey::from_slice(&[1; 32]).unwrap()
+    }
+
+    fn ed25519_key() -> ed25519_dalek::SigningKey {
+        ed25519_dalek::SigningKey::from_bytes(&[2; 32])
+    }
+
+    fn preimage_hash() -> Hash {
+        let mut k = Keccak::v256();
+        let mut hash = [0; 32];
+        k.update(PREIMAGE);
+        k.finalize(&mut hash);
+        hash
+    }
+
+    /// Returns the policy: passkey AND (ledger OR 2-of-3 guardians).
+    fn policy() -> Policy {
+        Policy::And(vec![
+            Policy::P256 {
+                pub_key: p256_key()
+                    .verifying_key()
+                    .to_encoded_point(true)
+                    .as_bytes()
+                    .to_vec(),
+            },
+            Policy::Or(vec![
+                Policy::Secp256k1 {
+                    signer: address(&signing_key(1)),
+                },
+                Policy::Threshold {
+                    k: 2,
+                    policies: vec![
+                        Policy::Ed25519 {
+                            pub_key: ed25519_key().verifying_key().to_bytes(),
+                        },
+                        Policy::Secp256k1 {
+                            signer: address(&signing_key(2)),
+                        },
+                        Policy::Preimage {
+                            hash: preimage_hash(),
+                        },
+                    ],
+                },
+            ]),
+        ])
+    }
+
+    fn secp256k1_sig(seed: u8, msg_hash: &Hash) -> Witness {
+        let (sig, recid) = signing_key(seed)
+            .sign_prehash_recoverable(msg_hash)
+            .unwrap();
+        Witness::Secp256k1(KSignature {
+            sig: sig.to_bytes().into(),
+            recid: recid.to_byte(),
+        })
+    }
+
+    fn p256_sig(msg_hash: &Hash) -> Witness {
+        let sig: p256::ecdsa::Signature = p256_key().sign_prehash(msg_hash).unwrap();
+        Witness::P256(sig.to_bytes().to_vec())
+    }
+
+    fn ed25519_sig(msg_hash: &Hash) -> Witness {
+        Witness::Ed25519(ed25519_key().sign(msg_hash).to_bytes().to_vec())
+    }
+
+    /// Returns the inputs satisfying the [policy] with the witness built by `witness`.
+    fn inputs(witness: impl FnOnce(&Hash) -> Witness) -> Inputs {
+        let policy = policy();
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &policy.to_bytes());
+        let new_key = [42; 32];
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            policy,
+            witness: witness(&Program::msg_hash(&keyspace_id, &new_key)),
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_run_passkey_and_ledger() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![
+                Some(p256_sig(msg_hash)),
+                Some(Witness::Node(vec![Some(secp256k1_sig(1, msg_hash)), None])),
+            ])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_run_passkey_and_guardians() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![
+                Some(p256_sig(msg_hash)),
+                Some(Witness::Node(vec![
+                    None,
+                    Some(Witness::Node(vec![
+                        Some(ed25519_sig(msg_hash)),
+                        None,
+                        Some(Witness::Preimage(PREIMAGE.to_vec())),
+                    ])),
+                ])),
+            ])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "AND policy not satisfied")]
+    fn test_run_missing_passkey() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![
+                None,
+                Some(Witness::Node(vec![Some(secp256k1_sig(1, msg_hash)), None])),
+            ])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "threshold policy not satisfied: 1 of 2")]
+    fn test_run_guardians_below_threshold() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![
+                Some(p256_sig(msg_hash)),
+                Some(Witness::Node(vec![
+                    None,
+                    Some(Witness::Node(vec![
+                        None,
+                        Some(secp256k1_sig(2, msg_hash)),
+                        None,
+                    ])),
+                ])),
+            ])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid secp256k1 signer")]
+    fn test_run_invalid_signer() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![
+                Some(p256_sig(msg_hash)),
+                Some(Witness::Node(vec![Some(secp256k1_sig(2, msg_hash)), None])),
+            ])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid P-256 signature")]
+    fn test_run_invalid_passkey_signature() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![
+                Some(p256_sig(&[0; 32])),
+                Some(Witness::Node(vec![Some(secp256k1_sig(1, msg_hash)), None])),
+            ])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "policy does not match with current_key")]
+    fn test_run_invalid_policy() {
+        let mut inputs = inputs(|_| Witness::Preimage(PREIMAGE.to_vec()));
+        inputs.policy = Policy::Preimage {
+            hash: preimage_hash(),
+        };
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "witness does not match the policy")]
+    fn test_run_witness_shape_mismatch() {
+        let inputs = inputs(|msg_hash| {
+            Witness::Node(vec![Some(p256_sig(msg_hash)), Some(p256_sig(msg_hash))])
+        });
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_evaluate_dry_run() {
+        let policy = Policy::Threshold {
+            k: 0,
+            policies: vec![Policy::Preimage {
+                hash: preimage_hash(),
+            }],
+        };
+
+        let err = policy
+            .evaluate(&[0; 32], &Witness::Node(vec![None]))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "invalid threshold policy");
+
+        let policy = Policy::Or(vec![Policy::Preimage {
+            hash: preimage_hash(),
+        }]);
+        assert!(policy
+            .evaluate(
+                &[0; 32],
+                &Witness::Node(vec![Some(Witness::Preimage(PREIMAGE.to_vec()))])
+            )
+            .is_ok());
+        assert!(policy
+            .evaluate(
+                &[0; 32],
+                &Witness::Node(vec![Some(Witness::Preimage(vec![]))])
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ecdsa_record::k_signature::KSignature, Hash};
+
+use super::storage::Storage;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Action {
+    /// The owner updates the record to `new_key`.
+    ///
+    /// This is also how the owner cancels a pending recovery.
+    Owner { sig: KSignature },
+    /// The guardians start a recovery towards `recovery_key`.
+    ///
+    /// `new_key` MUST be the key of the current storage with its pending recovery set to
+    /// (`recovery_key`, `started_at`). The record proof is only valid up to `started_at`.
+    StartRecovery {
+        recovery_key: Hash,
+        started_at: u64,
+        sigs: Vec<KSignature>,
+    },
+    /// The guardians complete the pending recovery.
+    ///
+    /// `new_key` MUST be the pending recovery key. The record proof is only valid once the
+    /// recovery delay has elapsed.
+    CompleteRecovery { sigs: Vec<KSignature> },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the record storage committed by `current_key`.
+    pub storage: Storage,
+    /// Private input: the action authorizing the update, signed over keccak(keyspace_id, new_key).
+    pub action: Action,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
@@ -0,0 +1,366 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    ecdsa_record::k_signature::KSignature, keyspace_key_from_storage, time_bounds::TimeBounds,
+};
+
+use super::{
+    inputs::{Action, Inputs},
+    storage::{PendingRecovery, Storage},
+};
+
+pub struct Program;
+
+impl Program {
+    /// Runs the recovery record program and returns the time bounds the record proof must commit to, if any.
+    pub fn run(inputs: &Inputs) -> Option<TimeBounds> {
+        // Ensure the provided storage is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.storage.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "storage does not match with current_key"
+        );
+
+        // Compute the `msg_hash`: keccack(keyspace_id, new_key).
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(&inputs.keyspace_id);
+        k.update(&inputs.new_key);
+        k.finalize(&mut msg_hash);
+
+        match &inputs.action {
+            Action::Owner { sig } => {
+                let signer = sig
+                    .ecrecover_address(&msg_hash)
+                    .expect("failed to recover the owner address");
+                assert_eq!(signer, inputs.storage.owner, "signer is not the owner");
+
+                None
+            }
+            Action::StartRecovery {
+                recovery_key,
+                started_at,
+                sigs,
+            } => {
+                check_guardians_quorum(&inputs.storage, &msg_hash, sigs);
+
+                // Ensure the `new_key` only sets the pending recovery.
+                let new_storage = Storage {
+                    pending: Some(PendingRecovery {
+                        new_key: *recovery_key,
+                        started_at: *started_at,
+                    }),
+                    ..inputs.storage.clone()
+                };
+                let new_key = keyspace_key_from_storage(&inputs.vk_hash, &new_storage.to_bytes());
+                assert_eq!(inputs.new_key, new_key, "invalid pending recovery");
+
+                // Prevent backdating the recovery: it can only be started before `started_at`.
+                Some(TimeBounds::until(*started_at))
+            }
+            Action::CompleteRecovery { sigs } => {
+                let pending = inputs
+                    .storage
+                    .pending
+                    .as_ref()
+                    .expect("no pending recovery");
+                assert_eq!(
+                    inputs.new_key, pending.new_key,
+                    "new_key does not match with the pending recovery"
+                );
+
+                check_guardians_quorum(&inputs.storage, &msg_hash, sigs);
+
+                // The recovery can only be completed once the delay has elapsed.
+                let valid_after = pending
+                    .started_at
+                    .checked_add(inputs.storage.delay)
+                    .expect("recovery delay overflow");
+                Some(TimeBounds::after(valid_after))
+            }
+        }
+    }
+}
+
+/// Ensures that at least `threshold` distinct guardians signed the `msg_hash`.
+fn check_guardians_quorum(storage: &Storage, msg_hash: &[u8; 32], sigs: &[KSignature]) {
+    assert!(storage.threshold > 0, "recovery is disabled");
+
+    let mut signers = Vec::with_capacity(sigs.len());
+    for sig in sigs {
+        let signer = sig
+            .ecrecover_address(msg_hash)
+            .expect("failed to recover the guardian address");
+
+        assert!(
+            storage.guardians.contains(&signer),
+            "signer is not a guardian"
+        );
+        assert!(!signers.contains(&signer), "duplicate guardian signature");
+
+        signers.push(signer);
+    }
+
+    assert!(
+        signers.len() >= storage.threshold as usize,
+        "not enough guardian signatures"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_utils::{address, sign, signing_key},
+        Hash,
+    };
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+    const DELAY: u64 = 3 * 24 * 3600;
+
+    fn storage() -> Storage {
+        Storage {
+            owner: address(&signing_key(1)),
+            guardians: vec![
+                address(&signing_key(2)),
+                address(&signing_key(3)),
+                address(&signing_key(4)),
+            ],
+            threshold: 2,
+            delay: DELAY,
+            pending: None,
+        }
+    }
+
+    fn key(storage: &Storage) -> Hash {
+        keyspace_key_from_storage(&VK_HASH, &storage.to_bytes())
+    }
+
+    /// Returns the inputs to start a recovery towards `recovery_key`, signed by the guardians `signers`.
+    fn start_recovery_inputs(started_at: u64, recovery_key: Hash, signers: &[u8]) -> Inputs {
+        let storage = storage();
+        let keyspace_id = key(&storage);
+        let new_key = key(&Storage {
+            pending: Some(PendingRecovery {
+                new_key: recovery_key,
+                started_at,
+            }),
+            ..storage.clone()
+        });
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::StartRecovery {
+                recovery_key,
+                started_at,
+                sigs: signers
+                    .iter()
+                    .map(|seed| sign(&signing_key(*seed), &keyspace_id, &new_key))
+                    .collect(),
+            },
+            storage,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    /// Returns the inputs to complete the recovery started by `start`, signed by the guardians `signers`.
+    fn complete_recovery_inputs(start: &Inputs, new_key: Hash, signers: &[u8]) -> Inputs {
+        let Action::StartRecovery {
+            recovery_key,
+            started_at,
+            ..
+        } = start.action
+        else {
+            unreachable!()
+        };
+
+        Inputs {
+            keyspace_id: start.keyspace_id,
+            current_key: start.new_key,
+            new_key,
+            action: Action::CompleteRecovery {
+                sigs: signers
+                    .iter()
+                    .map(|seed| sign(&signing_key(*seed), &start.keyspace_id, &new_key))
+                    .collect(),
+            },
+            storage: Storage {
+                pending: Some(PendingRecovery {
+                    new_key: recovery_key,
+                    started_at,
+                }),
+                ..storage()
+            },
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_owner_update() {
+        let storage = storage();
+        let keyspace_id = key(&storage);
+        let new_key = [42; 32];
+
+        let inputs = Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::Owner {
+                sig: sign(&signing_key(1), &keyspace_id, &new_key),
+            },
+            storage,
+            vk_hash: VK_HASH,
+        };
+
+        assert_eq!(Program::run(&inputs), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "storage does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = start_recovery_inputs(100, [42; 32], &[2, 3]);
+        inputs.storage.threshold = 1;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "signer is not the owner")]
+    fn test_owner_update_invalid_signer() {
+        let storage = storage();
+        let keyspace_id = key(&storage);
+        let new_key = [42; 32];
+
+        let inputs = Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::Owner {
+                sig: sign(&signing_key(2), &keyspace_id, &new_key),
+            },
+            storage,
+            vk_hash: VK_HASH,
+        };
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_start_recovery() {
+        let inputs = start_recovery_inputs(1_000, [42; 32], &[2, 4]);
+        assert_eq!(Program::run(&inputs), Some(TimeBounds::until(1_000)));
+    }
+
+    #[test]
+    #[should_panic(expected = "not enough guardian signatures")]
+    fn test_start_recovery_below_threshold() {
+        let inputs = start_recovery_inputs(1_000, [42; 32], &[3]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate guardian signature")]
+    fn test_start_recovery_duplicate_guardian() {
+        let inputs = start_recovery_inputs(1_000, [42; 32], &[3, 3]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "signer is not a guardian")]
+    fn test_start_recovery_invalid_guardian() {
+        let inputs = start_recovery_inputs(1_000, [42; 32], &[2, 5]);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid pending recovery")]
+    fn test_start_recovery_invalid_new_key() {
+        let mut inputs = start_recovery_inputs(1_000, [42; 32], &[2, 3]);
+
+        // Sign over a new key that does not record the pending recovery.
+        let new_key = [42; 32];
+        inputs.new_key = new_key;
+        inputs.action = Action::StartRecovery {
+            recovery_key: new_key,
+            started_at: 1_000,
+            sigs: vec![
+                sign(&signing_key(2), &inputs.keyspace_id, &new_key),
+                sign(&signing_key(3), &inputs.keyspace_id, &new_key),
+            ],
+        };
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_complete_recovery() {
+        let start = start_recovery_inputs(1_000, [42; 32], &[2, 3]);
+        let inputs = complete_recovery_inputs(&start, [42; 32], &[3, 4]);
+
+        assert_eq!(
+            Program::run(&inputs),
+            Some(TimeBounds::after(1_000 + DELAY))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "new_key does not match with the pending recovery")]
+    fn test_complete_recovery_invalid_new_key() {
+        let start = start_recovery_inputs(1_000, [42; 32], &[2, 3]);
+        let inputs = complete_recovery_inputs(&start, [43; 32], &[3, 4]);
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "no pending recovery")]
+    fn test_complete_recovery_without_pending() {
+        let storage = storage();
+        let keyspace_id = key(&storage);
+        let new_key = [42; 32];
+
+        let inputs = Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::CompleteRecovery {
+                sigs: vec![
+                    sign(&signing_key(2), &keyspace_id, &new_key),
+                    sign(&signing_key(3), &keyspace_id, &new_key),
+                ],
+            },
+            storage,
+            vk_hash: VK_HASH,
+        };
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_owner_cancels_recovery() {
+        let start = start_recovery_inputs(1_000, [42; 32], &[2, 3]);
+
+        // The owner rotates back to a storage without pending recovery.
+        let new_key = key(&storage());
+        let inputs = Inputs {
+            keyspace_id: start.keyspace_id,
+            current_key: start.new_key,
+            new_key,
+            action: Action::Owner {
+                sig: sign(&signing_key(1), &start.keyspace_id, &new_key),
+            },
+            storage: Storage {
+                pending: Some(PendingRecovery {
+                    new_key: [42; 32],
+                    started_at: 1_000,
+                }),
+                ..storage()
+            },
+            vk_hash: VK_HASH,
+        };
+
+        assert_eq!(Program::run(&inputs), None);
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ecdsa_record::k_signature::Address, Hash};
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PendingRecovery {
+    /// The Keyspace key the record will be rotated to once the recovery completes.
+    pub new_key: Hash,
+    /// The timestamp at which the recovery was started.
+    pub started_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Storage {
+    /// The owner address, allowed to update the record at any time.
+    pub owner: Address,
+    /// The guardian addresses, allowed to recover the record.
+    pub guardians: Vec<Address>,
+    /// The number of guardian signatures needed to start or complete a recovery.
+    pub threshold: u8,
+    /// The delay (in seconds) that must elapse between the start and the completion of a recovery.
+    pub delay: u64,
+    /// The recovery in progress, if any.
+    pub pending: Option<PendingRecovery>,
+}
+
+impl Storage {
+    /// Encodes the storage:
+    /// owner || delay || threshold || guardians_len || guardians || pending_flag [|| new_key || started_at].
+    ///
+    /// Integers are encoded in big endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20 + 8 + 2 + self.guardians.len() * 20 + 1 + 40);
+        bytes.extend_from_slice(&self.owner);
+        bytes.extend_from_slice(&self.delay.to_be_bytes());
+        bytes.push(self.threshold);
+        bytes.push(self.guardians.len().try_into().expect("too many guardians"));
+        for guardian in &self.guardians {
+            bytes.extend_from_slice(guardian);
+        }
+
+        match &self.pending {
+            Some(pending) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&pending.new_key);
+                bytes.extend_from_slice(&pending.started_at.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        bytes
+    }
+}
@@ -0,0 +1,17 @@
+use anyhow::{Context, Result};
+use rsa::{BigUint, Pkcs1v15Sign, RsaPublicKey};
+use sha2::{Digest, Sha256};
+
+/// The RSA public exponent used by the OIDC and DKIM key providers.
+pub const RSA_EXPONENT: u32 = 65537;
+
+/// Verifies an RSASSA-PKCS1-v1_5 SHA-256 `signature` over `msg` against the RSA key with the given
+/// `modulus` (big endian) and [RSA_EXPONENT].
+pub fn verify(modulus: &[u8], msg: &[u8], signature: &[u8]) -> Result<()> {
+    let key = RsaPublicKey::new(BigUint::from_bytes_be(modulus), BigUint::from(RSA_EXPONENT))
+        .context("invalid RSA key")?;
+
+    let digest = Sha256::digest(msg);
+    key.verify(Pkcs1v15Sign::new::<Sha256>(), &digest, signature)
+        .context("invalid RSA signature")
+}
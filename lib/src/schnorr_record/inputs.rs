@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the BIP-340 x-only public key, which is the record storage.
+    ///
+    /// It can be an aggregated MuSig2 key.
+    pub pub_key: Hash,
+    /// Private input: the 64 bytes BIP-340 signature over the tagged update message.
+    pub sig: Vec<u8>,
+    /// Private input: whether the proof approves a delegated update (see [crate::approval_public_values])
+    /// instead of updating the Keyspace id.
+    pub delegation_approval: bool,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
@@ -0,0 +1,262 @@
+use k256::schnorr::{Signature, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::{keyspace_key_from_storage, Hash};
+
+use super::inputs::Inputs;
+
+/// The BIP-340 tag of the update message.
+pub const UPDATE_TAG: &[u8] = b"Keyspace/update";
+/// The BIP-340 tag of the delegation approval message (see [crate::approval_public_values]).
+pub const APPROVAL_TAG: &[u8] = b"Keyspace/delegation-approval";
+
+pub struct Program;
+
+impl Program {
+    pub fn run(inputs: &Inputs) {
+        // Ensure the provided public key is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.pub_key);
+        assert_eq!(
+            inputs.current_key, current_key,
+            "pub_key does not match with current_key"
+        );
+
+        // Verify the signature over the tagged update message.
+        let pub_key = VerifyingKey::from_bytes(&inputs.pub_key).expect("invalid public key");
+        let sig = Signature::try_from(inputs.sig.as_slice()).expect("invalid signature");
+        pub_key
+            .verify_raw(
+                &Self::msg(
+                    &inputs.keyspace_id,
+                    &inputs.new_key,
+                    inputs.delegation_approval,
+                ),
+                &sig,
+            )
+            .expect("invalid signature");
+    }
+
+    /// Computes the update message to sign: the BIP-340 tagged hash of (keyspace_id, new_key) with
+    /// [UPDATE_TAG], or with [APPROVAL_TAG] for a delegation approval so that it can not be proven
+    /// as an update.
+    pub fn msg(keyspace_id: &Hash, new_key: &Hash, delegation_approval: bool) -> Hash {
+        let tag_hash = Sha256::digest(match delegation_approval {
+            true => APPROVAL_TAG,
+            false => UPDATE_TAG,
+        });
+
+        Sha256::new()
+            .chain_update(tag_hash)
+            .chain_update(tag_hash)
+            .chain_update(keyspace_id)
+            .chain_update(new_key)
+            .finalize()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::{
+        elliptic_curve::{ops::Reduce, point::AffineCoordinates, sec1::ToEncodedPoint},
+        schnorr::SigningKey,
+        ProjectivePoint, Scalar, U256,
+    };
+
+    use crate::test_utils::{schnorr_sign, schnorr_signing_key as signing_key};
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+
+    fn inputs(pub_key: Hash, new_key: Hash) -> Inputs {
+        let keyspace_id = keyspace_key_from_storage(&VK_HASH, &pub_key);
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            pub_key,
+            sig: vec![],
+            delegation_approval: false,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    fn sign(signing_key: &SigningKey, keyspace_id: &Hash, new_key: &Hash) -> Vec<u8> {
+        schnorr_sign(signing_key, &Program::msg(keyspace_id, new_key, false))
+    }
+
+    fn tagged_hash(tag: &[u8], data: &[&[u8]]) -> Scalar {
+        let tag_hash = Sha256::digest(tag);
+        let mut hasher = Sha256::new().chain_update(tag_hash).chain_update(tag_hash);
+        for data in data {
+            hasher.update(data);
+        }
+
+        <Scalar as Reduce<U256>>::reduce_bytes(&hasher.finalize())
+    }
+
+    fn negate_if_odd(point: &ProjectivePoint, scalar: Scalar) -> Scalar {
+        if point.to_affine().y_is_odd().into() {
+            -scalar
+        } else {
+            scalar
+        }
+    }
+
+    /// Aggregates the `secrets` keys and signs `msg` following BIP-327 (MuSig2), without tweaks.
+    ///
+    /// Returns the aggregated x-only public key and the final BIP-340 signature.
+    fn musig2_sign(secrets: &[Scalar], nonces: &[(Scalar, Scalar)], msg: &[u8]) -> (Hash, Vec<u8>) {
+        let pub_keys = secrets
+            .iter()
+            .map(|secret| {
+                (ProjectivePoint::GENERATOR * secret)
+                    .to_encoded_point(true)
+                    .as_bytes()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+
+        // KeyAgg: the second distinct key has a coefficient of 1.
+        let list_hash = Sha256::new()
+            .chain_update(Sha256::digest(b"KeyAgg list"))
+            .chain_update(Sha256::digest(b"KeyAgg list"))
+            .chain_update(pub_keys.concat())
+            .finalize();
+        let coefficients = pub_keys
+            .iter()
+            .map(
+                |pub_key| match pub_keys.iter().find(|pk| *pk != &pub_keys[0]) {
+                    Some(second) if second == pub_key => Scalar::ONE,
+                    _ => tagged_hash(b"KeyAgg coefficient", &[&list_hash, pub_key]),
+                },
+            )
+            .collect::<Vec<_>>();
+        let q = secrets
+            .iter()
+            .zip(&coefficients)
+            .fold(ProjectivePoint::IDENTITY, |q, (secret, a)| {
+                q + ProjectivePoint::GENERATOR * (*a * secret)
+            });
+        let q_x: Hash = q.to_affine().x().into();
+
+        // Nonce aggregation.
+        let r1 = nonces.iter().fold(ProjectivePoint::IDENTITY, |r, (k1, _)| {
+            r + ProjectivePoint::GENERATOR * k1
+        });
+        let r2 = nonces.iter().fold(ProjectivePoint::IDENTITY, |r, (_, k2)| {
+            r + ProjectivePoint::GENERATOR * k2
+        });
+        let agg_nonce = [r1, r2]
+            .iter()
+            .flat_map(|r| r.to_encoded_point(true).as_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let b = tagged_hash(b"MuSig/noncecoef", &[&agg_nonce, &q_x, msg]);
+        let r = r1 + r2 * b;
+        let r_x: Hash = r.to_affine().x().into();
+        let e = tagged_hash(b"BIP0340/challenge", &[&r_x, &q_x, msg]);
+
+        // Partial signatures and aggregation.
+        let s = secrets.iter().zip(&coefficients).zip(nonces).fold(
+            Scalar::ZERO,
+            |s, ((secret, a), (k1, k2))| {
+                let k = negate_if_odd(&r, *k1 + b * k2);
+                let d = negate_if_odd(&q, *secret);
+                s + k + e * a * d
+            },
+        );
+
+        (q_x, [r_x.to_vec(), s.to_bytes().to_vec()].concat())
+    }
+
+    #[test]
+    fn test_run() {
+        let signing_key = signing_key(1);
+        let mut inputs = inputs(signing_key.verifying_key().to_bytes().into(), [42; 32]);
+        inputs.sig = sign(&signing_key, &inputs.keyspace_id, &inputs.new_key);
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_run_musig2_key() {
+        let secrets = [
+            Scalar::from(11u64),
+            Scalar::from(22u64),
+            Scalar::from(33u64),
+        ];
+        let nonces = [
+            (Scalar::from(101u64), Scalar::from(102u64)),
+            (Scalar::from(201u64), Scalar::from(202u64)),
+            (Scalar::from(301u64), Scalar::from(302u64)),
+        ];
+
+        let (agg_key, _) = musig2_sign(&secrets, &nonces, &[]);
+        let mut inputs = inputs(agg_key, [42; 32]);
+        let (_, sig) = musig2_sign(
+            &secrets,
+            &nonces,
+            &Program::msg(&inputs.keyspace_id, &inputs.new_key, false),
+        );
+        inputs.sig = sig;
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_run_delegation_approval() {
+        let signing_key = signing_key(1);
+        let mut inputs = inputs(signing_key.verifying_key().to_bytes().into(), [42; 32]);
+        inputs.delegation_approval = true;
+        inputs.sig = schnorr_sign(
+            &signing_key,
+            &Program::msg(&inputs.keyspace_id, &inputs.new_key, true),
+        );
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature")]
+    fn test_run_delegation_approval_as_update() {
+        let signing_key = signing_key(1);
+        let mut inputs = inputs(signing_key.verifying_key().to_bytes().into(), [42; 32]);
+        inputs.sig = schnorr_sign(
+            &signing_key,
+            &Program::msg(&inputs.keyspace_id, &inputs.new_key, true),
+        );
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature")]
+    fn test_run_invalid_signer() {
+        let mut inputs = inputs(signing_key(1).verifying_key().to_bytes().into(), [42; 32]);
+        inputs.sig = sign(&signing_key(2), &inputs.keyspace_id, &inputs.new_key);
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature")]
+    fn test_run_invalid_new_key() {
+        let signing_key = signing_key(1);
+        let mut inputs = inputs(signing_key.verifying_key().to_bytes().into(), [42; 32]);
+        inputs.sig = sign(&signing_key, &inputs.keyspace_id, &[43; 32]);
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "pub_key does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(signing_key(1).verifying_key().to_bytes().into(), [42; 32]);
+        inputs.sig = sign(&signing_key(1), &inputs.keyspace_id, &inputs.new_key);
+        inputs.pub_key = signing_key(2).verifying_key().to_bytes().into();
+
+        Program::run(&inputs);
+    }
+}
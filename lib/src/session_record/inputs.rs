@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ecdsa_record::k_signature::KSignature, Hash};
+
+use super::storage::Storage;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Action {
+    /// The master key updates the record to `new_key`.
+    ///
+    /// This is also how the master key sets, rotates or revokes the session key.
+    Master { sig: KSignature },
+    /// The session key updates the record to `new_storage`.
+    ///
+    /// `new_storage` MUST keep the same master key and MUST NOT extend the session key expiry.
+    /// The record proof is only valid until the session key expires.
+    Session {
+        sig: KSignature,
+        new_storage: Storage,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the record storage committed by `current_key`.
+    pub storage: Storage,
+    /// Private input: the action authorizing the update, signed over keccak(keyspace_id, new_key).
+    pub action: Action,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
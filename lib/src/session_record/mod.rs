@@ -0,0 +1,3 @@
+pub mod inputs;
+pub mod program;
+pub mod storage;
@@ -0,0 +1,223 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{keyspace_key_from_storage, time_bounds::TimeBounds};
+
+use super::inputs::{Action, Inputs};
+
+pub struct Program;
+
+impl Program {
+    /// Runs the session record program and returns the time bounds the record proof must commit to, if any.
+    pub fn run(inputs: &Inputs) -> Option<TimeBounds> {
+        // Ensure the provided storage is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.storage.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "storage does not match with current_key"
+        );
+
+        // Compute the `msg_hash`: keccack(keyspace_id, new_key).
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(&inputs.keyspace_id);
+        k.update(&inputs.new_key);
+        k.finalize(&mut msg_hash);
+
+        match &inputs.action {
+            Action::Master { sig } => {
+                let signer = sig
+                    .ecrecover_address(&msg_hash)
+                    .expect("failed to recover the master address");
+                assert_eq!(signer, inputs.storage.master, "signer is not the master");
+
+                None
+            }
+            Action::Session { sig, new_storage } => {
+                let session = inputs.storage.session.as_ref().expect("no session key");
+
+                let signer = sig
+                    .ecrecover_address(&msg_hash)
+                    .expect("failed to recover the session address");
+                assert_eq!(signer, session.key, "signer is not the session key");
+
+                // Ensure the session key can not take over the record.
+                let new_key = keyspace_key_from_storage(&inputs.vk_hash, &new_storage.to_bytes());
+                assert_eq!(inputs.new_key, new_key, "invalid new storage");
+                assert_eq!(
+                    new_storage.master, inputs.storage.master,
+                    "session key can not change the master key"
+                );
+                if let Some(new_session) = &new_storage.session {
+                    assert!(
+                        new_session.expires_at <= session.expires_at,
+                        "session key can not extend its expiry"
+                    );
+                }
+
+                // The session key can only be used before it expires.
+                let valid_until = session
+                    .expires_at
+                    .checked_sub(1)
+                    .expect("session key expired");
+                Some(TimeBounds::until(valid_until))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        session_record::storage::{Session, Storage},
+        test_utils::{address, sign, signing_key},
+        Hash,
+    };
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+    const EXPIRES_AT: u64 = 1_000;
+
+    fn storage(session: Option<(u8, u64)>) -> Storage {
+        Storage {
+            master: address(&signing_key(1)),
+            session: session.map(|(seed, expires_at)| Session {
+                key: address(&signing_key(seed)),
+                expires_at,
+            }),
+        }
+    }
+
+    fn key(storage: &Storage) -> Hash {
+        keyspace_key_from_storage(&VK_HASH, &storage.to_bytes())
+    }
+
+    /// Returns the inputs for the session key `signer` to update the record to `new_storage`.
+    fn session_inputs(signer: u8, new_storage: Storage) -> Inputs {
+        let storage = storage(Some((2, EXPIRES_AT)));
+        let keyspace_id = key(&storage);
+        let new_key = key(&new_storage);
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::Session {
+                sig: sign(&signing_key(signer), &keyspace_id, &new_key),
+                new_storage,
+            },
+            storage,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    #[test]
+    fn test_master_update() {
+        let storage = storage(Some((2, EXPIRES_AT)));
+        let keyspace_id = key(&storage);
+
+        // Revoke the session key.
+        let new_key = key(&self::storage(None));
+
+        let inputs = Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::Master {
+                sig: sign(&signing_key(1), &keyspace_id, &new_key),
+            },
+            storage,
+            vk_hash: VK_HASH,
+        };
+
+        assert_eq!(Program::run(&inputs), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "storage does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = session_inputs(2, storage(Some((3, EXPIRES_AT))));
+        inputs.storage.session = None;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "signer is not the master")]
+    fn test_master_update_invalid_signer() {
+        let storage = storage(Some((2, EXPIRES_AT)));
+        let keyspace_id = key(&storage);
+        let new_key = [42; 32];
+
+        let inputs = Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::Master {
+                sig: sign(&signing_key(2), &keyspace_id, &new_key),
+            },
+            storage,
+            vk_hash: VK_HASH,
+        };
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_session_update() {
+        // Rotate the session key.
+        let inputs = session_inputs(2, storage(Some((3, EXPIRES_AT))));
+        assert_eq!(
+            Program::run(&inputs),
+            Some(TimeBounds::until(EXPIRES_AT - 1))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "signer is not the session key")]
+    fn test_session_update_invalid_signer() {
+        let inputs = session_inputs(3, storage(Some((3, EXPIRES_AT))));
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "session key can not change the master key")]
+    fn test_session_update_changes_master() {
+        let new_storage = Storage {
+            master: address(&signing_key(2)),
+            session: None,
+        };
+
+        let inputs = session_inputs(2, new_storage);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "session key can not extend its expiry")]
+    fn test_session_update_extends_expiry() {
+        let inputs = session_inputs(2, storage(Some((2, EXPIRES_AT + 1))));
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "no session key")]
+    fn test_session_update_without_session() {
+        let storage = storage(None);
+        let keyspace_id = key(&storage);
+        let new_storage = self::storage(Some((2, EXPIRES_AT)));
+        let new_key = key(&new_storage);
+
+        let inputs = Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            action: Action::Session {
+                sig: sign(&signing_key(2), &keyspace_id, &new_key),
+                new_storage,
+            },
+            storage,
+            vk_hash: VK_HASH,
+        };
+
+        Program::run(&inputs);
+    }
+}
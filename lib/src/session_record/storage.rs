@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ecdsa_record::k_signature::Address;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Session {
+    /// The session key address.
+    pub key: Address,
+    /// The timestamp from which the session key can no longer be used.
+    pub expires_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Storage {
+    /// The master key address, allowed to update the record at any time.
+    pub master: Address,
+    /// The session key, allowed to update the record until it expires.
+    pub session: Option<Session>,
+}
+
+impl Storage {
+    /// Encodes the storage: master || session_flag [|| session_key || expires_at].
+    ///
+    /// Integers are encoded in big endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20 + 1 + 28);
+        bytes.extend_from_slice(&self.master);
+
+        match &self.session {
+            Some(session) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&session.key);
+                bytes.extend_from_slice(&session.expires_at.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+
+        bytes
+    }
+}
@@ -0,0 +1,70 @@
+//! Helpers shared by the record programs tests.
+
+use k256::{ecdsa::SigningKey, schnorr};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    ecdsa_record::k_signature::{Address, KSignature},
+    Hash,
+};
+
+/// Returns the secp256k1 signing key whose secret is `seed` repeated.
+pub fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_slice(&[seed; 32]).unwrap()
+}
+
+/// Returns the secp256k1 signing key whose secret is the integer `secret`.
+pub fn signing_key_from_int(secret: u8) -> SigningKey {
+    let mut bytes = [0; 32];
+    bytes[31] = secret;
+    SigningKey::from_slice(&bytes).unwrap()
+}
+
+/// Returns the uncompressed public key of `signing_key`, without its 0x04 prefix.
+pub fn pub_key(signing_key: &SigningKey) -> [u8; 64] {
+    signing_key
+        .verifying_key()
+        .to_encoded_point(false)
+        .as_bytes()[1..]
+        .try_into()
+        .unwrap()
+}
+
+/// Returns the Ethereum address of `signing_key`.
+pub fn address(signing_key: &SigningKey) -> Address {
+    let mut k = Keccak::v256();
+    let mut pub_key_hash = [0; 32];
+    k.update(&pub_key(signing_key));
+    k.finalize(&mut pub_key_hash);
+
+    pub_key_hash[12..].try_into().unwrap()
+}
+
+/// Signs keccak(keyspace_id || new_key) with `signing_key`.
+pub fn sign(signing_key: &SigningKey, keyspace_id: &Hash, new_key: &Hash) -> KSignature {
+    let mut k = Keccak::v256();
+    let mut msg_hash = [0; 32];
+    k.update(keyspace_id);
+    k.update(new_key);
+    k.finalize(&mut msg_hash);
+
+    let (sig, recid) = signing_key.sign_prehash_recoverable(&msg_hash).unwrap();
+    KSignature {
+        sig: sig.to_bytes().into(),
+        recid: recid.to_byte(),
+    }
+}
+
+/// Returns the BIP-340 signing key whose secret is `seed` repeated.
+pub fn schnorr_signing_key(seed: u8) -> schnorr::SigningKey {
+    schnorr::SigningKey::from_bytes(&[seed; 32]).unwrap()
+}
+
+/// Signs `msg` with `signing_key`, without auxiliary randomness.
+pub fn schnorr_sign(signing_key: &schnorr::SigningKey, msg: &[u8]) -> Vec<u8> {
+    signing_key
+        .sign_raw(msg, &[0; 32])
+        .unwrap()
+        .to_bytes()
+        .to_vec()
+}
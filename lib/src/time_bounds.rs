@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+/// A validity window, in seconds since the Unix epoch, that record programs can commit next to
+/// the standard record public values.
+///
+/// The batcher only applies a record proof carrying time bounds if its batch timestamp falls
+/// within them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TimeBounds {
+    /// The first timestamp (inclusive) at which the record proof can be applied.
+    pub valid_after: u64,
+    /// The last timestamp (inclusive) at which the record proof can be applied.
+    pub valid_until: u64,
+}
+
+impl TimeBounds {
+    pub fn new(valid_after: u64, valid_until: u64) -> Self {
+        Self {
+            valid_after,
+            valid_until,
+        }
+    }
+
+    /// Returns bounds that are valid from `valid_after` onwards.
+    pub fn after(valid_after: u64) -> Self {
+        Self::new(valid_after, u64::MAX)
+    }
+
+    /// Returns bounds that are valid up to `valid_until`.
+    pub fn until(valid_until: u64) -> Self {
+        Self::new(0, valid_until)
+    }
+
+    pub fn contains(&self, timestamp: u64) -> bool {
+        self.valid_after <= timestamp && timestamp <= self.valid_until
+    }
+
+    /// Encodes the bounds as committed by record programs: valid_after || valid_until (big endian).
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.valid_after.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.valid_until.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; 16]) -> Self {
+        Self::new(
+            u64::from_be_bytes(bytes[..8].try_into().unwrap()),
+            u64::from_be_bytes(bytes[8..].try_into().unwrap()),
+        )
+    }
+}
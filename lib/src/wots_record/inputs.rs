@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+use super::storage::Storage;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Inputs {
+    /// Public input: the Keyspace id.
+    pub keyspace_id: Hash,
+    /// Public input: the Keyspace current key.
+    pub current_key: Hash,
+    /// Public input: the Keyspace new key.
+    pub new_key: Hash,
+
+    /// Private input: the record storage.
+    pub storage: Storage,
+    /// Private input: the record new storage, which must commit to a fresh one-time key.
+    pub new_storage: Storage,
+    /// Private input: the index of the one-time key used to sign.
+    pub index: u32,
+    /// Private input: the WOTS signature over keccack(keyspace_id, new_key).
+    pub sig: Vec<Hash>,
+    /// Private input: the Merkle path of the one-time key leaf, from the leaf level up to the root.
+    pub path: Vec<Hash>,
+    /// Private input: the verifier key hash.
+    pub vk_hash: Hash,
+}
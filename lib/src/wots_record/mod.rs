@@ -0,0 +1,4 @@
+pub mod inputs;
+pub mod program;
+pub mod storage;
+pub mod wots;
@@ -0,0 +1,233 @@
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{keyspace_key_from_storage, merkle::compute_root};
+
+use super::{inputs::Inputs, wots::leaf_from_signature};
+
+pub struct Program;
+
+impl Program {
+    pub fn run(inputs: &Inputs) {
+        // Ensure the provided storage is the one committed by the `current_key`.
+        let current_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.storage.to_bytes());
+        assert_eq!(
+            inputs.current_key, current_key,
+            "storage does not match with current_key"
+        );
+
+        // Ensure the one-time key has not been used by a previous update.
+        assert!(
+            inputs.index >= inputs.storage.next_index,
+            "one-time key already used"
+        );
+
+        // Compute the `msg_hash`: keccack(keyspace_id, new_key).
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(&inputs.keyspace_id);
+        k.update(&inputs.new_key);
+        k.finalize(&mut msg_hash);
+
+        // Ensure the signature is from a one-time key of the tree.
+        let leaf = leaf_from_signature(
+            &inputs.storage.pub_seed,
+            inputs.index,
+            &msg_hash,
+            &inputs.sig,
+        )
+        .expect("invalid signature length");
+        let root = compute_root(&leaf, inputs.index, &inputs.path);
+        assert_eq!(root, Some(inputs.storage.root), "invalid signature");
+
+        // Ensure the new storage commits to a fresh one-time key: either a new tree, or the same tree
+        // with the used keys discarded.
+        let new_key = keyspace_key_from_storage(&inputs.vk_hash, &inputs.new_storage.to_bytes());
+        assert_eq!(inputs.new_key, new_key, "invalid new storage");
+        if inputs.new_storage.root == inputs.storage.root {
+            assert!(
+                inputs.new_storage.next_index > inputs.index,
+                "new storage reuses the one-time key"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        merkle::node_hash,
+        wots_record::{
+            storage::Storage,
+            wots::{digits, WotsKey},
+        },
+        Hash,
+    };
+
+    use super::*;
+
+    const VK_HASH: Hash = [0xaa; 32];
+    const PUB_SEED: Hash = [3; 32];
+
+    fn key(secret_seed: u8, index: u32) -> WotsKey {
+        WotsKey {
+            secret_seed: [secret_seed; 32],
+            pub_seed: PUB_SEED,
+            index,
+        }
+    }
+
+    /// Builds the tree of the 4 one-time keys derived from `secret_seed` and returns its root and the
+    /// path of the leaf at `index`.
+    fn tree(secret_seed: u8, index: u32) -> (Hash, Vec<Hash>) {
+        let leaves = (0..4)
+            .map(|i| key(secret_seed, i).leaf())
+            .collect::<Vec<_>>();
+        let nodes = [
+            node_hash(&leaves[0], &leaves[1]),
+            node_hash(&leaves[2], &leaves[3]),
+        ];
+        let i = index as usize;
+
+        (
+            node_hash(&nodes[0], &nodes[1]),
+            vec![leaves[i ^ 1], nodes[(i / 2) ^ 1]],
+        )
+    }
+
+    fn key_of(storage: &Storage) -> Hash {
+        keyspace_key_from_storage(&VK_HASH, &storage.to_bytes())
+    }
+
+    /// Returns the inputs signed with the one-time key `index` to update to `new_storage`.
+    fn inputs(next_index: u32, index: u32, new_storage: Storage) -> Inputs {
+        let (root, path) = tree(1, index);
+        let storage = Storage {
+            pub_seed: PUB_SEED,
+            root,
+            next_index,
+        };
+        let keyspace_id = key_of(&storage);
+        let new_key = key_of(&new_storage);
+
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(&keyspace_id);
+        k.update(&new_key);
+        k.finalize(&mut msg_hash);
+
+        Inputs {
+            keyspace_id,
+            current_key: keyspace_id,
+            new_key,
+            storage,
+            new_storage,
+            index,
+            sig: key(1, index).sign(&msg_hash),
+            path,
+            vk_hash: VK_HASH,
+        }
+    }
+
+    fn same_tree(next_index: u32) -> Storage {
+        Storage {
+            pub_seed: PUB_SEED,
+            root: tree(1, 0).0,
+            next_index,
+        }
+    }
+
+    #[test]
+    fn test_run_next_key() {
+        Program::run(&inputs(0, 0, same_tree(1)));
+        Program::run(&inputs(1, 2, same_tree(3)));
+    }
+
+    #[test]
+    fn test_run_fresh_tree() {
+        let new_storage = Storage {
+            pub_seed: PUB_SEED,
+            root: tree(2, 0).0,
+            next_index: 0,
+        };
+
+        Program::run(&inputs(3, 3, new_storage));
+    }
+
+    #[test]
+    fn test_run_single_key() {
+        let storage = Storage {
+            pub_seed: PUB_SEED,
+            root: key(1, 0).leaf(),
+            next_index: 0,
+        };
+        let new_storage = Storage {
+            root: key(2, 0).leaf(),
+            ..storage.clone()
+        };
+
+        let mut inputs = inputs(0, 0, new_storage);
+        inputs.keyspace_id = key_of(&storage);
+        inputs.current_key = inputs.keyspace_id;
+        inputs.storage = storage;
+        inputs.path = vec![];
+
+        let mut k = Keccak::v256();
+        let mut msg_hash = [0; 32];
+        k.update(&inputs.keyspace_id);
+        k.update(&inputs.new_key);
+        k.finalize(&mut msg_hash);
+        inputs.sig = key(1, 0).sign(&msg_hash);
+
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "new storage reuses the one-time key")]
+    fn test_run_reuse_key() {
+        Program::run(&inputs(0, 1, same_tree(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "one-time key already used")]
+    fn test_run_used_key() {
+        Program::run(&inputs(2, 1, same_tree(3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature")]
+    fn test_run_forged_signature() {
+        let mut inputs = inputs(0, 0, same_tree(1));
+        inputs.sig[10][0] ^= 1;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid signature")]
+    fn test_run_invalid_index() {
+        let mut inputs = inputs(0, 0, same_tree(2));
+        inputs.index = 1;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "storage does not match with current_key")]
+    fn test_run_invalid_storage() {
+        let mut inputs = inputs(0, 0, same_tree(1));
+        inputs.storage.next_index = 1;
+        Program::run(&inputs);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid new storage")]
+    fn test_run_invalid_new_storage() {
+        let mut inputs = inputs(0, 0, same_tree(1));
+        inputs.new_storage = same_tree(2);
+        Program::run(&inputs);
+    }
+
+    #[test]
+    fn test_digits_checksum() {
+        assert_eq!(digits(&[0; 32])[64..], [3, 12, 0]);
+        assert_eq!(digits(&[0xff; 32])[64..], [0, 0, 0]);
+    }
+}
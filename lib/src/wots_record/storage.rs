@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Hash;
+
+/// The storage of a WOTS record: a Merkle tree of Winternitz one-time public keys (XMSS-style).
+///
+/// A single one-time key is a tree of height 0, whose root is the key leaf.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Storage {
+    /// The public seed, used to domain separate the hash chains of the tree.
+    pub pub_seed: Hash,
+    /// The Merkle root of the one-time public keys leaves.
+    pub root: Hash,
+    /// The index of the first unused one-time key.
+    pub next_index: u32,
+}
+
+impl Storage {
+    /// Encodes the storage as pub_seed || root || next_index.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(68);
+        bytes.extend_from_slice(&self.pub_seed);
+        bytes.extend_from_slice(&self.root);
+        bytes.extend_from_slice(&self.next_index.to_be_bytes());
+        bytes
+    }
+}
@@ -0,0 +1,124 @@
+//! Winternitz one-time signatures (WOTS) over keccack, with `w = 16` and 32 bytes messages.
+
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::Hash;
+
+/// The Winternitz parameter.
+pub const W: u8 = 16;
+/// The number of message chains: one per nibble.
+pub const LEN1: usize = 64;
+/// The number of checksum chains: the checksum is at most 64 * 15 = 960 < 16^3.
+pub const LEN2: usize = 3;
+/// The number of hash chains in a signature.
+pub const LEN: usize = LEN1 + LEN2;
+
+/// Applies `steps` steps of the hash chain `chain` of the one-time key `index` to `x`, starting from `start`.
+///
+/// Each step is keccack(pub_seed, index, chain, step, x).
+pub fn chain(pub_seed: &Hash, index: u32, chain: u8, x: &Hash, start: u8, steps: u8) -> Hash {
+    (start..start + steps).fold(*x, |x, step| {
+        let mut k = Keccak::v256();
+        let mut next = [0; 32];
+        k.update(pub_seed);
+        k.update(&index.to_be_bytes());
+        k.update(&[chain, step]);
+        k.update(&x);
+        k.finalize(&mut next);
+        next
+    })
+}
+
+/// Returns the base `W` digits of `msg`, followed by the ones of its checksum.
+pub fn digits(msg: &Hash) -> [u8; LEN] {
+    let mut digits = [0; LEN];
+    for (i, byte) in msg.iter().enumerate() {
+        digits[2 * i] = byte >> 4;
+        digits[2 * i + 1] = byte & 0x0f;
+    }
+
+    let checksum = digits[..LEN1]
+        .iter()
+        .map(|digit| (W - 1 - digit) as u16)
+        .sum::<u16>();
+    digits[LEN1] = (checksum >> 8) as u8 & 0x0f;
+    digits[LEN1 + 1] = (checksum >> 4) as u8 & 0x0f;
+    digits[LEN1 + 2] = checksum as u8 & 0x0f;
+
+    digits
+}
+
+/// Computes a one-time public key leaf from its chains ends: keccack(pub_seed, ends).
+fn leaf(pub_seed: &Hash, ends: impl Iterator<Item = Hash>) -> Hash {
+    let mut k = Keccak::v256();
+    let mut leaf = [0; 32];
+    k.update(pub_seed);
+    for end in ends {
+        k.update(&end);
+    }
+    k.finalize(&mut leaf);
+    leaf
+}
+
+/// Recovers the leaf of the one-time key `index` from a signature over `msg`.
+///
+/// Returns `None` if the signature does not have [LEN] chains.
+pub fn leaf_from_signature(pub_seed: &Hash, index: u32, msg: &Hash, sig: &[Hash]) -> Option<Hash> {
+    if sig.len() != LEN {
+        return None;
+    }
+
+    let ends = digits(msg)
+        .into_iter()
+        .zip(sig)
+        .enumerate()
+        .map(|(i, (digit, x))| chain(pub_seed, index, i as u8, x, digit, W - 1 - digit));
+
+    Some(leaf(pub_seed, ends))
+}
+
+/// A one-time key derived from a secret seed, used by the host to create and sign with WOTS records.
+pub struct WotsKey {
+    pub secret_seed: Hash,
+    pub pub_seed: Hash,
+    pub index: u32,
+}
+
+impl WotsKey {
+    /// Derives the secret of the hash chain `chain`: keccack(secret_seed, index, chain).
+    fn secret(&self, chain: u8) -> Hash {
+        let mut k = Keccak::v256();
+        let mut secret = [0; 32];
+        k.update(&self.secret_seed);
+        k.update(&self.index.to_be_bytes());
+        k.update(&[chain]);
+        k.finalize(&mut secret);
+        secret
+    }
+
+    /// Returns the one-time public key leaf.
+    pub fn leaf(&self) -> Hash {
+        let ends =
+            (0..LEN as u8).map(|i| chain(&self.pub_seed, self.index, i, &self.secret(i), 0, W - 1));
+
+        leaf(&self.pub_seed, ends)
+    }
+
+    /// Signs `msg`. The key must not be used to sign another message.
+    pub fn sign(&self, msg: &Hash) -> Vec<Hash> {
+        digits(msg)
+            .into_iter()
+            .enumerate()
+            .map(|(i, digit)| {
+                chain(
+                    &self.pub_seed,
+                    self.index,
+                    i as u8,
+                    &self.secret(i as u8),
+                    0,
+                    digit,
+                )
+            })
+            .collect()
+    }
+}
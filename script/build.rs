@@ -1,6 +1,17 @@
 use sp1_helper::build_program;
 
 fn main() {
+    build_program("../allowlist_record");
     build_program("../batcher");
+    build_program("../bip137_record");
+    build_program("../delegate_record");
     build_program("../ecdsa_record");
+    build_program("../email_record");
+    build_program("../hashlock_record");
+    build_program("../oidc_record");
+    build_program("../policy_record");
+    build_program("../recovery_record");
+    build_program("../schnorr_record");
+    build_program("../session_record");
+    build_program("../wots_record");
 }
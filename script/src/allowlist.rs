@@ -0,0 +1,118 @@
+use lib::{
+    merkle::{leaf_hash, node_hash},
+    Hash,
+};
+
+/// The Merkle tree of the public keys authorized by an allowlist record.
+///
+/// The leaves are padded with zero hashes up to the next power of two.
+pub struct AllowlistTree {
+    /// The tree layers, from the leaves up to the root.
+    layers: Vec<Vec<Hash>>,
+    pub_keys: Vec<Vec<u8>>,
+}
+
+impl AllowlistTree {
+    /// Builds the tree of the given uncompressed (64 bytes, without prefix) public keys.
+    pub fn new(pub_keys: &[Vec<u8>]) -> Self {
+        assert!(!pub_keys.is_empty(), "empty allowlist");
+        assert!(
+            pub_keys.iter().all(|pub_key| pub_key.len() == 64),
+            "invalid public key length"
+        );
+
+        let mut leaves = pub_keys
+            .iter()
+            .map(|pub_key| leaf_hash(pub_key))
+            .collect::<Vec<_>>();
+        leaves.resize(pub_keys.len().next_power_of_two(), [0; 32]);
+
+        let mut layers = vec![leaves];
+        while layers.last().unwrap().len() > 1 {
+            let layer = layers
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], &pair[1]))
+                .collect();
+            layers.push(layer);
+        }
+
+        Self {
+            layers,
+            pub_keys: pub_keys.to_vec(),
+        }
+    }
+
+    /// Returns the tree root, which is the allowlist record storage.
+    pub fn root(&self) -> Hash {
+        self.layers.last().unwrap()[0]
+    }
+
+    /// Returns the index of the given public key, if it is in the allowlist.
+    pub fn index_of(&self, pub_key: &[u8]) -> Option<u32> {
+        self.pub_keys
+            .iter()
+            .position(|k| k == pub_key)
+            .map(|index| index as u32)
+    }
+
+    /// Returns the Merkle path of the leaf at `index`, from the leaf level up to the root.
+    pub fn path(&self, index: u32) -> Vec<Hash> {
+        assert!((index as usize) < self.pub_keys.len(), "index out of range");
+
+        let mut index = index as usize;
+        self.layers[..self.layers.len() - 1]
+            .iter()
+            .map(|layer| {
+                let sibling = layer[index ^ 1];
+                index /= 2;
+                sibling
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::merkle::compute_root;
+
+    use super::*;
+
+    fn pub_keys(count: u8) -> Vec<Vec<u8>> {
+        (1..=count).map(|seed| vec![seed; 64]).collect()
+    }
+
+    #[test]
+    fn test_paths_verify_against_the_root() {
+        for count in 1..=5 {
+            let pub_keys = pub_keys(count);
+            let tree = AllowlistTree::new(&pub_keys);
+
+            for pub_key in &pub_keys {
+                let index = tree.index_of(pub_key).unwrap();
+                let path = tree.path(index);
+                assert_eq!(
+                    path.len(),
+                    (count as usize).next_power_of_two().ilog2() as usize
+                );
+                assert_eq!(
+                    compute_root(&leaf_hash(pub_key), index, &path),
+                    Some(tree.root())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_index_of_unknown_key() {
+        let tree = AllowlistTree::new(&pub_keys(3));
+        assert_eq!(tree.index_of(&[4; 64]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of range")]
+    fn test_path_of_padding_leaf() {
+        AllowlistTree::new(&pub_keys(3)).path(3);
+    }
+}
@@ -1,15 +1,24 @@
-use imt::circuits::imt::Imt;
-use sp1_sdk::{HashableKey, ProverClient, SP1Proof, SP1Stdin};
-
-use keyspace_script::load_record_proof_from_file;
-use lib::batcher::{inputs::Inputs, proof::Proof, tx::Tx};
-use tiny_keccak::Keccak;
+use std::{
+    env, fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sp1_sdk::{HashableKey, ProverClient, SP1Proof};
+
+use keyspace_script::{
+    ingest::{load_update_requests, onchain_txs, pending_requests},
+    load_record_proof_from_file,
+    receipt::onchain_receipts,
+    store::{BatchContext, Record, Store},
+    witness::BatchWitness,
+};
+use lib::{
+    batcher::{inputs::Inputs, proof::Proof, tx::Tx},
+    parse_record_public_values, RecordPublicValues,
+};
 
 pub const ELF: &[u8] = include_bytes!("../../../../batcher/elf/riscv32im-succinct-zkvm-elf");
 
-const ECDSA_RECORD_ELF: &[u8] =
-    include_bytes!("../../../../ecdsa_record/elf/riscv32im-succinct-zkvm-elf");
-
 fn main() {
     // Setup the logger.
     sp1_sdk::utils::setup_logger();
@@ -19,81 +28,178 @@ fn main() {
 
     // Setup the proving and verifying keys.
     let (batcher_pk, _) = client.setup(ELF);
-    let (_, record_vk) = client.setup(ECDSA_RECORD_ELF);
-
-    let mut tree = Imt::new(Keccak::v256);
-    let old_root = tree.root;
 
-    let mut stdin = SP1Stdin::new();
-
-    let mut tx_hash = [0; 32];
-    let txs = (0..10)
+    // Open the Keyspace state left by the previous batches.
+    let db = env::var("KEYSPACE_DB").unwrap_or_else(|_| String::from("keyspace.db"));
+    let mut store = Store::open(db).expect("failed to open the Keyspace store");
+    let old_root = store.root();
+    let onchain_queue_start = store
+        .onchain_queue_hash()
+        .expect("failed to read the Keyspace onchain queue hash");
+
+    // The SP1 record proofs, verified out of band.
+    let mut record_proofs = vec![];
+
+    let mut tx_hash = store
+        .tx_hash()
+        .expect("failed to read the Keyspace tx hash");
+    let mut txs = (0..10)
         .map(|i| {
             // Read the Record Proof from file storage.
-            let (storage_hash, record_proof, plonk_proof) =
+            // NOTE: The record proofs can be generated by different record programs.
+            let (storage_hash, storage, record_vk, record_proof, plonk_proof) =
                 load_record_proof_from_file(&format!("proofs/record_proof_{i}.json"));
 
-            // Fetch the KeySpace id and the new key from the record proof public inputs.
-            let keyspace_id = record_proof.public_values.as_slice()[..32]
-                .try_into()
-                .expect("invalid record proof public inputs");
-
-            let new_key = record_proof.public_values.as_slice()[64..]
-                .try_into()
+            // Fetch the KeySpace id, the new key, the optional time bounds and Keyspace root from the record proof public inputs.
+            let RecordPublicValues {
+                keyspace_id,
+                new_key,
+                time_bounds,
+                keyspace_root,
+                ..
+            } = parse_record_public_values(record_proof.public_values.as_slice())
                 .expect("invalid record proof public inputs");
 
-            // Generate the IMTMutate.
-            let imt_mutate = tree.insert_node(keyspace_id, new_key);
-
-            // Build an Offchain transaction to send.
-            let tx = match record_proof.proof {
-                SP1Proof::Compressed(proof) => {
-                    // SP1 proofs are verified out of band.
-                    stdin.write_proof(proof, record_vk.vk.clone());
-                    Tx::offchain(
-                        imt_mutate,
-                        tx_hash,
-                        Proof::sp1(record_vk.hash_bytes(), storage_hash),
-                    )
-                }
+            // Build the proof of the Offchain transaction.
+            let proof = match &record_proof.proof {
+                SP1Proof::Compressed(_proof) => Proof::sp1(
+                    record_vk.hash_bytes(),
+                    storage_hash,
+                    time_bounds,
+                    keyspace_root,
+                ),
                 SP1Proof::Plonk(_proof) => {
                     let verifiable_proof = plonk_proof.unwrap();
-                    Tx::offchain(
-                        imt_mutate,
-                        tx_hash,
-                        Proof::plonk(
-                            &verifiable_proof.vk,
-                            &verifiable_proof.proof,
-                            verifiable_proof.plonk_vk_hash,
-                            verifiable_proof.zkvm_vk_hash,
-                            storage_hash,
-                        ),
+                    Proof::plonk(
+                        &verifiable_proof.vk,
+                        &verifiable_proof.proof,
+                        verifiable_proof.plonk_vk_hash,
+                        verifiable_proof.zkvm_vk_hash,
+                        storage_hash,
+                        time_bounds,
+                        keyspace_root,
                     )
                 }
                 _ => panic!("record proof should be compressed to be recursively verified"),
             };
 
+            // Generate the IMTMutate, inserting the KeySpace id if it is not registered yet.
+            let record = Record {
+                vk_hash: proof
+                    .record_vk_hash()
+                    .expect("invalid record proof verifier key hash"),
+                storage_hash,
+                storage,
+            };
+            let imt_mutate = store
+                .set_key(keyspace_id, new_key, Some(record))
+                .expect("failed to update the Keyspace store");
+
+            // Build an Offchain transaction to send.
+            let tx = Tx::offchain(imt_mutate, tx_hash, proof);
+
+            if let SP1Proof::Compressed(_) = record_proof.proof {
+                // SP1 proofs are verified out of band.
+                record_proofs.push((record_proof, record_vk));
+            }
+
             tx_hash = tx.hash();
 
             tx
         })
         .collect::<Vec<_>>();
 
-    let new_root = tree.root;
+    // The batch timestamp, checked against the record proofs time bounds.
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("invalid system time")
+        .as_secs();
+
+    // Append the updates requested onchain and not consumed yet, read from the KeyStore contract
+    // logs.
+    if let Ok(logs) = env::var("ONCHAIN_LOGS") {
+        let keystore = env::var("KEYSTORE_ADDRESS")
+            .map(|keystore| {
+                hex::decode(keystore.trim_start_matches("0x"))
+                    .expect("invalid KEYSTORE_ADDRESS")
+                    .try_into()
+                    .expect("invalid KEYSTORE_ADDRESS")
+            })
+            .expect("KEYSTORE_ADDRESS must be set to read the onchain logs");
+        let requests =
+            load_update_requests(&logs, &keystore).expect("failed to read the onchain logs");
+        let requests = pending_requests(requests, &onchain_queue_start)
+            .expect("failed to read the pending onchain requests");
+        txs.extend(
+            onchain_txs(&mut store, requests, tx_hash, timestamp)
+                .expect("failed to build the onchain txs"),
+        );
+        tx_hash = txs.last().map_or(tx_hash, |tx| tx.hash());
+    }
+
+    let new_root = store.root();
+
+    // The L1 block the batch is built on, checked by the L1 contract.
+    let block_number = env::var("L1_BLOCK_NUMBER")
+        .map(|block_number| block_number.parse().expect("invalid L1_BLOCK_NUMBER"))
+        .unwrap_or_default();
+    let block_hash = env::var("L1_BLOCK_HASH")
+        .map(|block_hash| {
+            hex::decode(block_hash.trim_start_matches("0x"))
+                .expect("invalid L1_BLOCK_HASH")
+                .try_into()
+                .expect("invalid L1_BLOCK_HASH")
+        })
+        .unwrap_or_default();
 
-    let inputs = Inputs {
+    let mut inputs = Inputs {
         old_root,
         new_root,
         new_tx_hash: tx_hash,
+        timestamp,
+        block_number,
+        block_hash,
+        onchain_queue_start,
+        onchain_queue_end: onchain_queue_start,
+        onchain_results: vec![],
 
         txs,
     };
+    // The onchain txs are consumed from the onchain queue, in order, and their results committed.
+    inputs.onchain_queue_end = inputs.consumed_onchain_queue();
+    inputs.onchain_results = inputs.onchain_tx_results();
+
+    // Save the batch witness first, so that the batch can be proven again (see `reprove`).
+    let witness = BatchWitness::new(inputs, record_proofs);
+    let batch_number = store
+        .batches_count()
+        .expect("failed to read the Keyspace batches count");
+    fs::create_dir_all("batches").expect("failed to create the batches directory");
+    witness.save(&format!("batches/batch_{batch_number}.witness"));
 
     // Generate the proof for it.
-    stdin.write(&inputs);
-    client
-        .prove(&batcher_pk, stdin)
+    let batch_proof = client
+        .prove(&batcher_pk, witness.stdin())
         .plonk()
         .run()
         .expect("batcher proving failed");
+    witness.check_public_values(&batch_proof);
+
+    // Report whether the updates requested onchain landed.
+    let receipts = onchain_receipts(&witness.inputs, batch_proof.public_values.as_slice())
+        .expect("failed to decode the onchain receipts");
+    for receipt in receipts {
+        println!("{receipt}");
+    }
+
+    // Persist the new Keyspace state only once the batch is proven.
+    store
+        .commit_batch(&BatchContext {
+            new_tx_hash: witness.inputs.new_tx_hash,
+            timestamp,
+            block_number,
+            block_hash,
+            onchain_queue_hash: witness.inputs.onchain_queue_end,
+        })
+        .expect("failed to commit the batch to the Keyspace store");
 }
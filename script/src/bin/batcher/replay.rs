@@ -0,0 +1,28 @@
+use std::{env, process};
+
+use keyspace_script::{replay::replay_batch, store::Store, witness::BatchWitness};
+
+const USAGE: &str = "usage: replay <store> <batch witness>...";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let Some((store, batches)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        process::exit(1);
+    };
+
+    // Rebuild the Keyspace state by replaying the batches in order, on top of the store state.
+    let mut store = Store::open(store).expect("failed to open the Keyspace store");
+    for file in batches {
+        // The batch witnesses are the `batch_N.witness` files saved by `prove`.
+        let witness = BatchWitness::load(file);
+
+        let batch = replay_batch(&mut store, &witness.inputs)
+            .unwrap_or_else(|err| panic!("failed to replay {file}: {err:#}"));
+        println!(
+            "Replayed {file}: {} mutations, root 0x{}",
+            batch.mutations.len(),
+            hex::encode(batch.new_root)
+        );
+    }
+}
@@ -0,0 +1,43 @@
+use std::{env, process};
+
+use sp1_sdk::ProverClient;
+
+use keyspace_script::{receipt::onchain_receipts, witness::BatchWitness};
+
+pub const ELF: &[u8] = include_bytes!("../../../../batcher/elf/riscv32im-succinct-zkvm-elf");
+
+const USAGE: &str = "usage: reprove <batch witness>";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let [file] = &args[..] else {
+        eprintln!("{USAGE}");
+        process::exit(1);
+    };
+
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    // Initialize the proving client.
+    let client = ProverClient::new();
+
+    // Setup the proving and verifying keys.
+    let (batcher_pk, _) = client.setup(ELF);
+
+    // Load the batch witness saved by `prove_batcher`.
+    let witness = BatchWitness::load(file);
+
+    // Prove the exact same batch again.
+    let batch_proof = client
+        .prove(&batcher_pk, witness.stdin())
+        .plonk()
+        .run()
+        .expect("batcher proving failed");
+    witness.check_public_values(&batch_proof);
+
+    let receipts = onchain_receipts(&witness.inputs, batch_proof.public_values.as_slice())
+        .expect("failed to decode the onchain receipts");
+    for receipt in receipts {
+        println!("{receipt}");
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    env,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use sp1_sdk::ProverClient;
+use tokio::net::TcpListener;
+
+use keyspace_script::{
+    ingest::{load_block, load_update_requests},
+    sequencer::{rpc, sp1::Sp1BatchProver, Sequencer, SequencerConfig},
+    store::Store,
+};
+
+pub const ELF: &[u8] = include_bytes!("../../../../batcher/elf/riscv32im-succinct-zkvm-elf");
+
+#[tokio::main]
+async fn main() {
+    // Setup the logger.
+    sp1_sdk::utils::setup_logger();
+
+    let addr = env::var("SEQUENCER_ADDR").unwrap_or_else(|_| String::from("127.0.0.1:8545"));
+    let db = env::var("KEYSPACE_DB").unwrap_or_else(|_| String::from("keyspace.db"));
+    let config = SequencerConfig {
+        max_batch_size: env::var("BATCH_MAX_SIZE")
+            .map(|size| size.parse().expect("invalid BATCH_MAX_SIZE"))
+            .unwrap_or(10),
+        max_batch_age: env::var("BATCH_MAX_AGE")
+            .map(|age| age.parse().expect("invalid BATCH_MAX_AGE"))
+            .unwrap_or(60),
+        // The L1 block is read from an `eth_getBlockByNumber` output kept up to date out of band,
+        // once per batch.
+        l1_block: {
+            let file = env::var("L1_BLOCK").expect("L1_BLOCK must be set to build the batches");
+            Box::new(move || load_block(&file))
+        },
+        // The update requests are read from the KeyStore contract `eth_getLogs` output, kept up to
+        // date out of band: the batches must consume the onchain queue.
+        update_requests: {
+            let logs =
+                env::var("ONCHAIN_LOGS").expect("ONCHAIN_LOGS must be set to build the batches");
+            let keystore = env::var("KEYSTORE_ADDRESS")
+                .map(|keystore| {
+                    hex::decode(keystore.trim_start_matches("0x"))
+                        .expect("invalid KEYSTORE_ADDRESS")
+                        .try_into()
+                        .expect("invalid KEYSTORE_ADDRESS")
+                })
+                .expect("KEYSTORE_ADDRESS must be set to read the onchain logs");
+            Box::new(move || load_update_requests(&logs, &keystore))
+        },
+    };
+
+    let store = Store::open(db).expect("failed to open the Keyspace store");
+    let prover = Sp1BatchProver::new(ProverClient::new(), ELF, PathBuf::from("batches"));
+    let sequencer = Arc::new(Sequencer::new(store, prover, config));
+
+    // Cut and prove the batches in the background.
+    let batcher = sequencer.clone();
+    thread::spawn(move || loop {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("invalid system time")
+            .as_secs();
+
+        match batcher.tick(now) {
+            Ok(Some(batch_number)) => println!("Proved batch {batch_number}"),
+            Ok(None) => thread::sleep(Duration::from_secs(1)),
+            Err(err) => {
+                eprintln!("{err:#}");
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+    });
+
+    let listener = TcpListener::bind(&addr)
+        .await
+        .expect("failed to bind the sequencer address");
+    println!("Sequencer listening on {addr}");
+    rpc::serve(listener, sequencer)
+        .await
+        .expect("sequencer server failed");
+}
@@ -0,0 +1,74 @@
+use std::{env, fs, process};
+
+use sp1_sdk::{HashableKey, ProverClient};
+
+use keyspace_script::{snapshot::Snapshot, store::Store};
+
+pub const ELF: &[u8] = include_bytes!("../../../../batcher/elf/riscv32im-succinct-zkvm-elf");
+
+const USAGE: &str = "usage:
+    snapshot export <store> <snapshot>
+    snapshot import <snapshot> <store>
+    snapshot diff <old snapshot> <new snapshot>";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["export", store, file] => export(store, file),
+        ["import", file, store] => import(file, store),
+        ["diff", old, new] => diff(old, new),
+        _ => {
+            eprintln!("{USAGE}");
+            process::exit(1);
+        }
+    }
+}
+
+fn export(store: &str, file: &str) {
+    let store = Store::open(store).expect("failed to open the Keyspace store");
+
+    // The batcher verifier key the state was proven with.
+    let (_, vk) = ProverClient::new().setup(ELF);
+
+    let snapshot =
+        Snapshot::from_store(&store, vk.hash_bytes()).expect("failed to snapshot the store");
+    fs::write(file, snapshot.to_bytes()).expect("failed to write the snapshot");
+
+    println!(
+        "Exported {} ids at root 0x{}",
+        snapshot.entries.len(),
+        hex::encode(snapshot.root)
+    );
+}
+
+fn import(file: &str, store: &str) {
+    let snapshot = read(file);
+
+    // The snapshot root is recomputed when decoding it, and again when restoring the store.
+    let store = Store::restore(store, &snapshot).expect("failed to restore the Keyspace store");
+
+    println!(
+        "Imported {} ids at root 0x{} (batch {})",
+        snapshot.entries.len(),
+        hex::encode(store.root()),
+        snapshot.batch_number
+    );
+}
+
+fn diff(old: &str, new: &str) {
+    let (old, new) = (read(old), read(new));
+    let diff = old.diff(&new).expect("failed to diff the snapshots");
+
+    println!("Batches {} to {}", old.batch_number, new.batch_number);
+    for keyspace_id in diff.inserted {
+        println!("+ 0x{}", hex::encode(keyspace_id));
+    }
+    for keyspace_id in diff.updated {
+        println!("~ 0x{}", hex::encode(keyspace_id));
+    }
+}
+
+fn read(file: &str) -> Snapshot {
+    let bytes = fs::read(file).expect("failed to read the snapshot");
+    Snapshot::from_bytes(&bytes).expect("invalid snapshot")
+}
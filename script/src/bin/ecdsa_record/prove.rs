@@ -1,14 +1,16 @@
+use std::env;
+
 use k256::{ecdsa::SigningKey, elliptic_curve::rand_core::OsRng};
 use rand::Rng;
 use sp1_sdk::{
     HashableKey, ProverClient, SP1ProofWithPublicValues, SP1ProvingKey, SP1Stdin, SP1VerifyingKey,
 };
-use tiny_keccak::{Hasher, Keccak};
 
 use keyspace_script::{read_plonk_vk, save_record_proof_to_file};
 use lib::{
-    ecdsa_record::{inputs::Inputs, k_signature::KSignature},
-    keyspace_key_from_storage_hash,
+    ecdsa_record::{inputs::Inputs, k_signature::KSignature, program::Program, storage::storage},
+    hash_storage, keyspace_key_from_storage_hash,
+    time_bounds::TimeBounds,
 };
 
 pub const ELF: &[u8] = include_bytes!("../../../../ecdsa_record/elf/riscv32im-succinct-zkvm-elf");
@@ -28,16 +30,18 @@ fn main() {
     let (_plonk_vk, plonk_vk_hash) = read_plonk_vk();
 
     for i in 0..10 {
-        let (proof, storage_hash) = prove_random_record_as_plonk(&client, &pk, &plonk_vk_hash);
+        let (proof, storage) = prove_random_record_as_plonk(&client, &pk, &plonk_vk_hash);
 
         // Verify the proof.
         client.verify(&proof, &vk).expect("failed to verify proof");
 
         // Serialize the proof and write it to storage.
-        // NOTE: Also save the `storage_hash` as it is needed when building the actual txs.
+        // NOTE: Also save the storage, as its hash is needed when building the actual txs and the
+        // batcher records it.
         save_record_proof_to_file(
             &proof,
-            storage_hash,
+            &vk,
+            &storage,
             &format!("proofs/record_proof_{i}.json"),
         );
     }
@@ -47,8 +51,8 @@ fn prove_random_record_as_plonk(
     client: &ProverClient,
     pk: &SP1ProvingKey,
     vk_hash: &[u8; 32],
-) -> (SP1ProofWithPublicValues, [u8; 32]) {
-    let (storage_hash, inputs) = random_inputs(vk_hash);
+) -> (SP1ProofWithPublicValues, Vec<u8>) {
+    let (storage, inputs) = random_inputs(vk_hash);
 
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
@@ -60,15 +64,15 @@ fn prove_random_record_as_plonk(
         .plonk()
         .run()
         .expect("failed to generate proof");
-    (proof, storage_hash)
+    (proof, storage)
 }
 
 fn prove_random_record_as_sp1(
     client: ProverClient,
     pk: &SP1ProvingKey,
     vk: &SP1VerifyingKey,
-) -> (SP1ProofWithPublicValues, [u8; 32]) {
-    let (storage_hash, inputs) = random_inputs(&vk.hash_bytes());
+) -> (SP1ProofWithPublicValues, Vec<u8>) {
+    let (storage, inputs) = random_inputs(&vk.hash_bytes());
 
     // Setup the inputs.
     let mut stdin = SP1Stdin::new();
@@ -80,29 +84,21 @@ fn prove_random_record_as_sp1(
         .compressed()
         .run()
         .expect("failed to generate proof");
-    (proof, storage_hash)
+    (proof, storage)
 }
 
-fn random_inputs(vk_hash: &[u8; 32]) -> ([u8; 32], Inputs) {
+fn random_inputs(vk_hash: &[u8; 32]) -> (Vec<u8>, Inputs) {
     let signing_key = SigningKey::random(&mut OsRng);
     let verifying_key = signing_key.verifying_key();
 
-    let storage_hash = {
-        let pk = verifying_key.to_encoded_point(false);
-        let x = pk.x().unwrap();
-        let y = pk.y().unwrap();
-
-        let mut pk = [0; 64];
-        pk[..32].copy_from_slice(x);
-        pk[32..].copy_from_slice(y);
-
-        let mut k = Keccak::v256();
-        let mut storage_hash = [0; 32];
-        k.update(&pk);
-        k.finalize(&mut storage_hash);
+    let pub_key: [u8; 64] = verifying_key.to_encoded_point(false).as_bytes()[1..]
+        .try_into()
+        .unwrap();
 
-        storage_hash
-    };
+    // The optional salt, allowing a single key to own several Keyspace ids.
+    let salt = salt();
+    let storage = storage(&pub_key, salt.as_ref());
+    let storage_hash = hash_storage(&storage);
 
     let keyspace_id = keyspace_key_from_storage_hash(&vk_hash, &storage_hash);
     let current_key = keyspace_id;
@@ -110,7 +106,8 @@ fn random_inputs(vk_hash: &[u8; 32]) -> ([u8; 32], Inputs) {
     let mut rng = rand::thread_rng();
     let new_key = rng.gen::<[u8; 32]>();
 
-    let sig = sign_update(&signing_key, &keyspace_id, &new_key);
+    let time_bounds = None;
+    let sig = sign_update(&signing_key, &keyspace_id, &new_key, time_bounds.as_ref());
 
     let inputs = Inputs {
         keyspace_id,
@@ -118,22 +115,35 @@ fn random_inputs(vk_hash: &[u8; 32]) -> ([u8; 32], Inputs) {
         new_key,
 
         sig,
+        salt,
+        time_bounds,
+        delegation_approval: false,
         vk_hash: *vk_hash,
     };
 
-    (storage_hash, inputs)
+    (storage, inputs)
 }
 
-fn sign_update(signing_key: &SigningKey, keyspace_id: &[u8; 32], new_key: &[u8; 32]) -> KSignature {
-    let msg_hash = {
-        let mut k = Keccak::v256();
-        let mut msg_hash = [0; 32];
-        k.update(keyspace_id);
-        k.update(new_key);
-        k.finalize(&mut msg_hash);
+/// Reads the optional ECDSA record storage salt from the `ECDSA_RECORD_SALT` env variable.
+fn salt() -> Option<[u8; 32]> {
+    env::var("ECDSA_RECORD_SALT")
+        .ok()
+        .filter(|salt| !salt.is_empty())
+        .map(|salt| {
+            hex::decode(salt.trim_start_matches("0x"))
+                .expect("invalid ECDSA_RECORD_SALT")
+                .try_into()
+                .expect("invalid ECDSA_RECORD_SALT")
+        })
+}
 
-        msg_hash
-    };
+fn sign_update(
+    signing_key: &SigningKey,
+    keyspace_id: &[u8; 32],
+    new_key: &[u8; 32],
+    time_bounds: Option<&TimeBounds>,
+) -> KSignature {
+    let msg_hash = Program::msg_hash(keyspace_id, new_key, time_bounds, false);
 
     let (sig, recid) = signing_key.sign_prehash_recoverable(&msg_hash).unwrap();
     let sig_bytes = sig.to_bytes();
@@ -0,0 +1,34 @@
+use k256::elliptic_curve::rand_core::{OsRng, RngCore};
+use lib::{hashlock_record::storage::lock, keyspace_key_from_storage, Hash};
+
+/// An offline backup opening a hash-lock record.
+pub struct HashLockBackup {
+    pub secret: Hash,
+    pub salt: Option<Hash>,
+}
+
+impl HashLockBackup {
+    /// Generates a random secret, and a random salt if `salted` is set.
+    pub fn generate(salted: bool) -> Self {
+        let mut secret = [0; 32];
+        OsRng.fill_bytes(&mut secret);
+
+        let salt = salted.then(|| {
+            let mut salt = [0; 32];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        });
+
+        Self { secret, salt }
+    }
+
+    /// Returns the hash lock, which is the record storage.
+    pub fn lock(&self) -> Hash {
+        lock(&self.secret, self.salt.as_ref())
+    }
+
+    /// Returns the Keyspace id of a hash-lock record created with this backup.
+    pub fn keyspace_id(&self, vk_hash: &Hash) -> Hash {
+        keyspace_key_from_storage(vk_hash, &self.lock())
+    }
+}
@@ -0,0 +1,476 @@
+use std::fs;
+
+use anyhow::{bail, ensure, Context, Result};
+use lib::{
+    batcher::{
+        proof::plonk::PLONKProof,
+        tx::{
+            onchain::{next_queue_hash, request_hash, OnchainTx, OnchainTxResult},
+            Tx,
+        },
+    },
+    time_bounds::TimeBounds,
+    Hash, DELEGATION_APPROVAL,
+};
+use num_bigint::BigUint;
+use serde::Deserialize;
+use serde_json::Value;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::store::{Record, Store};
+
+/// The event emitted by the KeyStore contract when an update is requested onchain:
+///
+/// ```solidity
+/// event KeyspaceUpdateRequested(
+///     bytes32 indexed keyspaceId,
+///     bytes32 newKey,
+///     bytes32 storageHash,
+///     uint256 plonkVkHash,
+///     uint256 zkvmVkHash,
+///     bytes extraPublicValues, // The record time bounds and Keyspace root, if committed.
+///     bytes vk,
+///     bytes proof
+/// );
+/// ```
+pub const UPDATE_REQUESTED_EVENT: &str =
+    "KeyspaceUpdateRequested(bytes32,bytes32,bytes32,uint256,uint256,bytes,bytes,bytes)";
+
+/// An update requested onchain, decoded from a `KeyspaceUpdateRequested` log.
+#[derive(Debug)]
+pub struct UpdateRequest {
+    pub block_number: u64,
+    pub log_index: u64,
+    pub keyspace_id: Hash,
+    pub new_key: Hash,
+    /// The PLONK record proof, verified by the batcher program.
+    pub proof: PLONKProof,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Log {
+    address: String,
+    topics: Vec<String>,
+    data: String,
+    block_number: String,
+    log_index: String,
+    #[serde(default)]
+    removed: bool,
+}
+
+/// The L1 block a batch is built on, checked by the L1 contract.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct L1Block {
+    pub number: u64,
+    pub hash: Hash,
+}
+
+#[derive(Deserialize)]
+struct Block {
+    number: String,
+    hash: String,
+}
+
+/// Returns the topic of [UPDATE_REQUESTED_EVENT] logs.
+pub fn update_requested_topic() -> Hash {
+    let mut k = Keccak::v256();
+    k.update(UPDATE_REQUESTED_EVENT.as_bytes());
+
+    let mut topic = [0; 32];
+    k.finalize(&mut topic);
+    topic
+}
+
+/// Reads the update requests emitted by the `keystore` contract from an `eth_getLogs` JSON output
+/// saved to `file`.
+pub fn load_update_requests(file: &str, keystore: &[u8; 20]) -> Result<Vec<UpdateRequest>> {
+    let logs = fs::read_to_string(file).with_context(|| format!("failed to read {file}"))?;
+    parse_update_requests(&logs, keystore)
+}
+
+/// Decodes the update requests emitted by the `keystore` contract from an `eth_getLogs` JSON
+/// output: either the JSON-RPC response or its bare `result` array.
+///
+/// The logs of other contracts, of other events and the removed logs (chain reorganizations) are
+/// ignored: any contract can emit a log with the [UPDATE_REQUESTED_EVENT] topic. The requests are
+/// returned in the order they were emitted.
+pub fn parse_update_requests(logs: &str, keystore: &[u8; 20]) -> Result<Vec<UpdateRequest>> {
+    let logs = rpc_result(logs, "eth_getLogs")?;
+    let logs: Vec<Log> = serde_json::from_value(logs).context("invalid eth_getLogs logs")?;
+
+    let address = format!("0x{}", hex::encode(keystore));
+    let topic = format!("0x{}", hex::encode(update_requested_topic()));
+    let mut requests = logs
+        .iter()
+        .filter(|log| {
+            !log.removed
+                && log.address.eq_ignore_ascii_case(&address)
+                && log.topics.first() == Some(&topic)
+        })
+        .map(decode_update_requested)
+        .collect::<Result<Vec<_>>>()?;
+    requests.sort_by_key(|request| (request.block_number, request.log_index));
+
+    Ok(requests)
+}
+
+/// Reads the L1 block from an `eth_getBlockByNumber` JSON output saved to `file`.
+pub fn load_block(file: &str) -> Result<L1Block> {
+    let block = fs::read_to_string(file).with_context(|| format!("failed to read {file}"))?;
+    parse_block(&block)
+}
+
+/// Decodes the L1 block from an `eth_getBlockByNumber` JSON output: either the JSON-RPC response
+/// or its bare `result` object.
+pub fn parse_block(block: &str) -> Result<L1Block> {
+    let block = rpc_result(block, "eth_getBlockByNumber")?;
+    ensure!(!block.is_null(), "unknown L1 block");
+    let block: Block =
+        serde_json::from_value(block).context("invalid eth_getBlockByNumber block")?;
+
+    Ok(L1Block {
+        number: decode_quantity(&block.number)?,
+        hash: decode_hex(&block.hash)?
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("invalid block hash"))?,
+    })
+}
+
+/// Returns the update `requests` not consumed yet from the onchain queue, given the `consumed`
+/// queue hash.
+///
+/// The `requests` must start from the KeyStore contract deployment: the queue hash is rebuilt from
+/// the first request until reaching the `consumed` position.
+pub fn pending_requests(
+    requests: Vec<UpdateRequest>,
+    consumed: &Hash,
+) -> Result<Vec<UpdateRequest>> {
+    let mut queue_hash = Hash::default();
+    let mut requests = requests.into_iter();
+    while queue_hash != *consumed {
+        let request = requests
+            .next()
+            .context("the logs do not reach the consumed onchain queue position")?;
+        queue_hash = next_queue_hash(
+            &queue_hash,
+            &request_hash(&request.keyspace_id, &request.new_key, &request.proof),
+        );
+    }
+
+    Ok(requests.collect())
+}
+
+/// Converts the update `requests` not consumed yet (see [pending_requests]), in queue order, into
+/// onchain txs chained after `prev_tx_hash`, and stages the mutations of the ones the batcher
+/// program applies in the `store`.
+///
+/// An update request is applied if its record proof reads the Keyspace from the committed root,
+/// is valid at the batch `timestamp` and controls the id current key. The other requests are still
+/// included, to be skipped by the batcher program: their IMTMutate is built on the staged state,
+/// but not staged.
+pub fn onchain_txs(
+    store: &mut Store,
+    requests: Vec<UpdateRequest>,
+    mut prev_tx_hash: Hash,
+    timestamp: u64,
+) -> Result<Vec<Tx>> {
+    let accepted_root = store.committed_root()?;
+
+    let mut txs = Vec::with_capacity(requests.len());
+    for UpdateRequest {
+        keyspace_id,
+        new_key,
+        proof,
+        ..
+    } in requests
+    {
+        let imt_mutate = store.probe_key(keyspace_id, new_key)?;
+        let tx = OnchainTx {
+            imt_mutate,
+            prev_tx_hash,
+            proof,
+        };
+
+        let result = match tx.proof.keyspace_root {
+            Some(DELEGATION_APPROVAL) => OnchainTxResult::DelegationApproval,
+            Some(keyspace_root) if keyspace_root != accepted_root => {
+                OnchainTxResult::KeyspaceRootNotAccepted
+            }
+            _ => tx.record_proof_result(timestamp),
+        };
+        if result.is_applied() {
+            let record = Record {
+                vk_hash: tx.proof.record_vk_hash().context("invalid plonk_vk_hash")?,
+                storage_hash: tx.proof.storage_hash,
+                storage: None,
+            };
+            store.set_key(keyspace_id, new_key, Some(record))?;
+        }
+
+        prev_tx_hash = tx.hash();
+        txs.push(Tx::Onchain(tx));
+    }
+
+    Ok(txs)
+}
+
+fn decode_update_requested(log: &Log) -> Result<UpdateRequest> {
+    let [_, keyspace_id] = log.topics.as_slice() else {
+        bail!("invalid KeyspaceUpdateRequested topics");
+    };
+    let keyspace_id = decode_hex(keyspace_id)?
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("invalid keyspaceId topic"))?;
+    let data = decode_hex(&log.data)?;
+
+    let extra_public_values = abi_bytes(&data, 4)?;
+    let (time_bounds, keyspace_root) = match extra_public_values.len() {
+        0 => (None, None),
+        16 => (Some(&extra_public_values[..16]), None),
+        32 => (None, Some(&extra_public_values[..32])),
+        48 => (
+            Some(&extra_public_values[..16]),
+            Some(&extra_public_values[16..48]),
+        ),
+        len => bail!("invalid extra public values length: {len}"),
+    };
+
+    Ok(UpdateRequest {
+        block_number: decode_quantity(&log.block_number)?,
+        log_index: decode_quantity(&log.log_index)?,
+        keyspace_id,
+        new_key: abi_word(&data, 0)?,
+        proof: PLONKProof {
+            vk: abi_bytes(&data, 5)?.to_vec(),
+            proof: abi_bytes(&data, 6)?.to_vec(),
+            plonk_vk_hash: BigUint::from_bytes_be(&abi_word(&data, 2)?).to_str_radix(10),
+            zkvm_vk_hash: BigUint::from_bytes_be(&abi_word(&data, 3)?).to_str_radix(10),
+            storage_hash: abi_word(&data, 1)?,
+            time_bounds: time_bounds
+                .map(|time_bounds| TimeBounds::from_bytes(time_bounds.try_into().unwrap())),
+            keyspace_root: keyspace_root.map(|keyspace_root| keyspace_root.try_into().unwrap()),
+        },
+    })
+}
+
+/// Returns the `index`-th word of the ABI encoded `data`.
+fn abi_word(data: &[u8], index: usize) -> Result<Hash> {
+    let word = data
+        .get(index * 32..(index + 1) * 32)
+        .context("truncated log data")?;
+    Ok(word.try_into().unwrap())
+}
+
+/// Returns the `bytes` value whose offset is the `index`-th word of the ABI encoded `data`.
+fn abi_bytes(data: &[u8], index: usize) -> Result<&[u8]> {
+    let offset = abi_usize(&abi_word(data, index)?)?;
+    ensure!(offset % 32 == 0, "invalid bytes offset in log data");
+    let len = abi_usize(&abi_word(data, offset / 32)?)?;
+
+    let start = offset + 32;
+    data.get(start..start.saturating_add(len))
+        .context("truncated log data")
+}
+
+fn abi_usize(word: &Hash) -> Result<usize> {
+    ensure!(
+        word[..24].iter().all(|byte| *byte == 0),
+        "invalid length or offset in log data"
+    );
+    Ok(u64::from_be_bytes(word[24..].try_into().unwrap()) as usize)
+}
+
+/// Returns the result of a JSON-RPC `method` call from its JSON `output`: either the JSON-RPC
+/// response or its bare result.
+fn rpc_result(output: &str, method: &str) -> Result<Value> {
+    let output: Value =
+        serde_json::from_str(output).with_context(|| format!("invalid {method} output"))?;
+    match output {
+        Value::Object(mut response)
+            if response.contains_key("result") || response.contains_key("error") =>
+        {
+            if let Some(error) = response.get("error") {
+                bail!("{method} failed: {error}");
+            }
+            Ok(response.remove("result").unwrap())
+        }
+        result => Ok(result),
+    }
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>> {
+    hex::decode(value.trim_start_matches("0x")).with_context(|| format!("invalid hex: {value}"))
+}
+
+fn decode_quantity(value: &str) -> Result<u64> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16)
+        .with_context(|| format!("invalid quantity: {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::store::BatchContext;
+
+    use super::*;
+
+    const LOGS: &str = include_str!("../fixtures/update_requested_logs.json");
+
+    fn address(address: &str) -> [u8; 20] {
+        hex::decode(address).unwrap().try_into().unwrap()
+    }
+
+    /// Returns the KeyStore contract emitting the fixture logs.
+    fn keystore() -> [u8; 20] {
+        address("5fbdb2315678afecb367f032d93f642f64180aa3")
+    }
+
+    #[test]
+    fn test_parse_update_requests() {
+        let requests = parse_update_requests(LOGS, &keystore()).unwrap();
+
+        // The removed log, the log of another event and the log of another contract are ignored,
+        // and the requests are sorted.
+        let positions = requests
+            .iter()
+            .map(|request| (request.block_number, request.log_index))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, [(0x10, 1), (0x10, 3), (0x11, 0)]);
+
+        let request = &requests[0];
+        assert_eq!(request.keyspace_id, [0x11; 32]);
+        assert_eq!(request.new_key, [0x12; 32]);
+        assert_eq!(request.proof.storage_hash, [0x13; 32]);
+        assert_eq!(request.proof.plonk_vk_hash, "42");
+        assert_eq!(request.proof.zkvm_vk_hash, "256");
+        assert_eq!(request.proof.vk, [0xaa; 4]);
+        assert_eq!(request.proof.proof, [0xbb; 40]);
+        assert_eq!(request.proof.time_bounds, None);
+        assert_eq!(request.proof.keyspace_root, None);
+
+        let request = &requests[1];
+        assert_eq!(request.keyspace_id, [0x21; 32]);
+        assert_eq!(request.proof.time_bounds, Some(TimeBounds::new(100, 200)));
+        assert_eq!(request.proof.keyspace_root, Some([0x24; 32]));
+
+        // The bare result array is accepted as well.
+        let result = serde_json::from_str::<Value>(LOGS).unwrap()["result"].to_string();
+        assert_eq!(
+            parse_update_requests(&result, &keystore()).unwrap().len(),
+            3
+        );
+
+        // Only the logs of the given contract are decoded.
+        let foreign = address("e7f1725e7734ce288f8367e1bb143e90bb3f0512");
+        let requests = parse_update_requests(LOGS, &foreign).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].keyspace_id, [0x91; 32]);
+    }
+
+    #[test]
+    fn test_parse_invalid_logs() {
+        let err =
+            parse_update_requests(r#"{ "error": { "code": -32000 } }"#, &keystore()).unwrap_err();
+        assert!(err.to_string().starts_with("eth_getLogs failed"));
+
+        let mut logs = serde_json::from_str::<Value>(LOGS).unwrap();
+        let data = logs["result"][0]["data"].as_str().unwrap();
+        logs["result"][0]["data"] = data[..data.len() - 64].into();
+        let err = parse_update_requests(&logs.to_string(), &keystore()).unwrap_err();
+        assert_eq!(err.to_string(), "truncated log data");
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let hash = format!("0x{}", "ab".repeat(32));
+        let expected = L1Block {
+            number: 0x1b4,
+            hash: [0xab; 32],
+        };
+
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": { "number": "0x1b4", "hash": hash, "parentHash": hash },
+        });
+        assert_eq!(parse_block(&response.to_string()).unwrap(), expected);
+
+        // The bare result object is accepted as well.
+        let result = response["result"].to_string();
+        assert_eq!(parse_block(&result).unwrap(), expected);
+
+        let response = json!({ "jsonrpc": "2.0", "id": 1, "result": null });
+        let err = parse_block(&response.to_string()).unwrap_err();
+        assert_eq!(err.to_string(), "unknown L1 block");
+    }
+
+    #[test]
+    fn test_onchain_txs_chaining() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::open(dir.path().join("keyspace.db")).unwrap();
+        let root = store.root();
+        let requests = parse_update_requests(LOGS, &keystore()).unwrap();
+        let requests = pending_requests(requests, &[0; 32]).unwrap();
+
+        // The fixture record proofs do not control their ids: the txs are skipped, but their
+        // IMTMutate is still built on the Keyspace state.
+        let txs = onchain_txs(&mut store, requests, [0xf0; 32], 150).unwrap();
+        assert_eq!(txs.len(), 3);
+        assert_eq!(store.root(), root);
+        for tx in &txs {
+            assert!(tx.verify_imt_mutate(&root).is_ok());
+        }
+
+        let mut prev_tx_hash = [0xf0; 32];
+        for tx in &txs {
+            let Tx::Onchain(onchain) = tx else {
+                panic!("expected an onchain tx");
+            };
+            assert_eq!(onchain.prev_tx_hash, prev_tx_hash);
+            prev_tx_hash = tx.hash();
+        }
+        assert_eq!(txs[1].keyspace_update(), ([0x21; 32], [0x22; 32]));
+    }
+
+    #[test]
+    fn test_pending_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::open(dir.path().join("keyspace.db")).unwrap();
+
+        // A previous batch consumed the first request.
+        let requests = parse_update_requests(LOGS, &keystore()).unwrap();
+        let txs = onchain_txs(&mut store, requests, [0; 32], 0).unwrap();
+        let Tx::Onchain(first) = &txs[0] else {
+            panic!("expected an onchain tx");
+        };
+        store
+            .commit_batch(&BatchContext {
+                new_tx_hash: txs[0].hash(),
+                timestamp: 0,
+                block_number: 0,
+                block_hash: [0; 32],
+                onchain_queue_hash: first.next_queue_hash(&[0; 32]),
+            })
+            .unwrap();
+
+        let requests = pending_requests(
+            parse_update_requests(LOGS, &keystore()).unwrap(),
+            &store.onchain_queue_hash().unwrap(),
+        )
+        .unwrap();
+        let txs = onchain_txs(&mut store, requests, [0; 32], 0).unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].keyspace_update().0, [0x21; 32]);
+
+        // The logs must start from the contract deployment.
+        let mut requests = parse_update_requests(LOGS, &keystore()).unwrap();
+        requests.remove(0);
+        let err = pending_requests(requests, &store.onchain_queue_hash().unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the logs do not reach the consumed onchain queue position"
+        );
+    }
+}
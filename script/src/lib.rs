@@ -4,15 +4,33 @@ use std::{
     path::PathBuf,
 };
 
-use lib::Hash;
+use lib::{hash_storage, Hash};
 use num_bigint::BigUint;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use sp1_sdk::{SP1Proof, SP1ProofWithPublicValues};
+use sp1_sdk::{SP1Proof, SP1ProofWithPublicValues, SP1VerifyingKey};
+
+pub mod allowlist;
+pub mod hashlock;
+pub mod ingest;
+pub mod receipt;
+pub mod replay;
+pub mod sequencer;
+pub mod snapshot;
+pub mod store;
+pub mod witness;
 
 #[derive(Serialize, Deserialize)]
 struct StorageProof {
     storage_hash: Hash,
+    /// The record storage, recorded as the preimage of the new key by the batcher.
+    #[serde(default)]
+    storage: Option<Vec<u8>>,
+    /// The verifying key of the record program that generated the proof.
+    ///
+    /// Record proofs of a Keyspace id are not all generated by the same program: an id can be
+    /// migrated to another record program (see [lib::keyspace_key_from_storage]).
+    record_vk: SP1VerifyingKey,
     // FIXME: Why serialize this as strings instead of their actual types?
     serialized_proof: String,
     serialized_plonk: Option<String>,
@@ -27,11 +45,18 @@ pub struct VerifiablePlonkProof {
     pub public_inputs_digest: String,
 }
 
-pub fn save_record_proof_to_file(proof: &SP1ProofWithPublicValues, storage_hash: Hash, file: &str) {
+pub fn save_record_proof_to_file(
+    proof: &SP1ProofWithPublicValues,
+    record_vk: &SP1VerifyingKey,
+    storage: &[u8],
+    file: &str,
+) {
     let serialized_proof = serde_json::to_string(&proof).expect("failed to serialize proof");
     let serialized_plonk = serialize_plonk(proof);
     let proof = StorageProof {
-        storage_hash,
+        storage_hash: hash_storage(storage),
+        storage: Some(storage.to_vec()),
+        record_vk: record_vk.clone(),
         serialized_proof,
         serialized_plonk,
     };
@@ -44,29 +69,60 @@ pub fn save_record_proof_to_file(proof: &SP1ProofWithPublicValues, storage_hash:
 
 pub fn load_record_proof_from_file(
     file: &str,
-) -> (Hash, SP1ProofWithPublicValues, Option<VerifiablePlonkProof>) {
+) -> (
+    Hash,
+    Option<Vec<u8>>,
+    SP1VerifyingKey,
+    SP1ProofWithPublicValues,
+    Option<VerifiablePlonkProof>,
+) {
     let mut file = File::open(file).expect("failed to open file");
 
     let mut proof = String::new();
     file.read_to_string(&mut proof)
         .expect("failed to read proof from storage");
 
-    let storage_proof: StorageProof =
-        serde_json::from_str(&proof).expect("failed to deserialize storage proof");
+    parse_record_proof(&proof).expect("failed to deserialize record proof")
+}
+
+/// Parses a record proof, as saved by [save_record_proof_to_file].
+///
+/// The record storage is optional, as it is not needed to verify the proof.
+pub fn parse_record_proof(
+    proof: &str,
+) -> anyhow::Result<(
+    Hash,
+    Option<Vec<u8>>,
+    SP1VerifyingKey,
+    SP1ProofWithPublicValues,
+    Option<VerifiablePlonkProof>,
+)> {
+    let storage_proof: StorageProof = serde_json::from_str(proof)?;
+    if let Some(storage) = &storage_proof.storage {
+        anyhow::ensure!(
+            hash_storage(storage) == storage_proof.storage_hash,
+            "storage does not match with storage_hash"
+        );
+    }
 
     let record_proof: SP1ProofWithPublicValues =
-        serde_json::from_str(&storage_proof.serialized_proof).expect("failed to deserialize proof");
+        serde_json::from_str(&storage_proof.serialized_proof)?;
 
     let plonk_proof = match storage_proof.serialized_plonk {
         Some(plonk_proof) => {
-            let plonk_proof: VerifiablePlonkProof =
-                serde_json::from_str(&plonk_proof).expect("failed to deserialize plonk proof");
+            let plonk_proof: VerifiablePlonkProof = serde_json::from_str(&plonk_proof)?;
             Some(plonk_proof)
         }
         None => None,
     };
 
-    (storage_proof.storage_hash, record_proof, plonk_proof)
+    Ok((
+        storage_proof.storage_hash,
+        storage_proof.storage,
+        storage_proof.record_vk,
+        record_proof,
+        plonk_proof,
+    ))
 }
 
 pub fn read_plonk_vk() -> (Vec<u8>, [u8; 32]) {
@@ -0,0 +1,156 @@
+use std::fmt;
+
+use anyhow::{ensure, Result};
+use lib::{
+    batcher::{
+        inputs::{parse_onchain_results, Inputs},
+        tx::{onchain::OnchainTxResult, Tx},
+    },
+    Hash,
+};
+
+/// The outcome of an update requested onchain, as committed by the batch that consumed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnchainReceipt {
+    /// The hash of the update request, as enqueued by the L1 contract.
+    pub request_hash: Hash,
+    pub keyspace_id: Hash,
+    pub new_key: Hash,
+    /// Whether the update was applied, or why it was skipped.
+    pub result: OnchainTxResult,
+}
+
+impl fmt::Display for OnchainReceipt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Onchain request 0x{} (id 0x{}): {}",
+            hex::encode(self.request_hash),
+            hex::encode(self.keyspace_id),
+            self.result
+        )
+    }
+}
+
+/// Returns the receipts of the onchain txs of a batch, in order, from its `inputs` and the
+/// `public_values` committed by its proof.
+pub fn onchain_receipts(inputs: &Inputs, public_values: &[u8]) -> Result<Vec<OnchainReceipt>> {
+    let results = parse_onchain_results(public_values)?;
+    let onchain_txs = inputs
+        .txs
+        .iter()
+        .filter(|tx| matches!(tx, Tx::Onchain(_)))
+        .collect::<Vec<_>>();
+    ensure!(
+        results.len() == onchain_txs.len(),
+        "the batch commits {} onchain tx results for {} onchain txs",
+        results.len(),
+        onchain_txs.len()
+    );
+
+    let receipts = onchain_txs
+        .into_iter()
+        .zip(results)
+        .map(|(tx, result)| {
+            let Tx::Onchain(onchain) = tx else {
+                unreachable!();
+            };
+            let (keyspace_id, new_key) = tx.keyspace_update();
+
+            OnchainReceipt {
+                request_hash: onchain.request_hash(),
+                keyspace_id,
+                new_key,
+                result,
+            }
+        })
+        .collect();
+
+    Ok(receipts)
+}
+
+#[cfg(test)]
+mod tests {
+    use imt::circuits::imt::Imt;
+    use lib::batcher::proof::{plonk::PLONKProof, Proof};
+    use tiny_keccak::Keccak;
+
+    use super::*;
+
+    fn onchain(keyspace_id: Hash) -> Tx {
+        Tx::onchain(
+            Imt::new(Keccak::v256).insert_node(keyspace_id, [42; 32]),
+            [0; 32],
+            PLONKProof {
+                vk: vec![],
+                proof: vec![],
+                plonk_vk_hash: "0".to_string(),
+                zkvm_vk_hash: "0".to_string(),
+                storage_hash: [0; 32],
+                time_bounds: None,
+                keyspace_root: None,
+            },
+        )
+    }
+
+    fn batch(txs: Vec<Tx>, onchain_results: Vec<OnchainTxResult>) -> Inputs {
+        Inputs {
+            old_root: [0; 32],
+            new_root: [0; 32],
+            new_tx_hash: [0; 32],
+            timestamp: 0,
+            block_number: 0,
+            block_hash: [0; 32],
+            onchain_queue_start: [0; 32],
+            onchain_queue_end: [0; 32],
+            onchain_results,
+            txs,
+        }
+    }
+
+    #[test]
+    fn test_onchain_receipts() {
+        let txs = vec![
+            onchain([1; 32]),
+            Tx::offchain(
+                Imt::new(Keccak::v256).insert_node([2; 32], [20; 32]),
+                [0; 32],
+                Proof::sp1([0; 32], [0; 32], None, None),
+            ),
+            onchain([3; 32]),
+        ];
+        let inputs = batch(
+            txs,
+            vec![OnchainTxResult::Applied, OnchainTxResult::NotControlled],
+        );
+
+        let receipts = onchain_receipts(&inputs, &inputs.public_values()).unwrap();
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[0].keyspace_id, [1; 32]);
+        assert_eq!(receipts[0].result, OnchainTxResult::Applied);
+        assert_eq!(receipts[1].keyspace_id, [3; 32]);
+        assert_eq!(receipts[1].new_key, [42; 32]);
+        assert_eq!(receipts[1].result, OnchainTxResult::NotControlled);
+        let Tx::Onchain(tx) = &inputs.txs[2] else {
+            unreachable!();
+        };
+        assert_eq!(receipts[1].request_hash, tx.request_hash());
+    }
+
+    #[test]
+    fn test_invalid_onchain_results() {
+        let inputs = batch(vec![onchain([1; 32])], vec![OnchainTxResult::InvalidProof]);
+
+        let mut public_values = inputs.public_values();
+        *public_values.last_mut().unwrap() = 0xff;
+        let err = onchain_receipts(&inputs, &public_values).unwrap_err();
+        assert_eq!(err.to_string(), "invalid onchain tx result code: 255");
+
+        public_values.pop();
+        let err = onchain_receipts(&inputs, &public_values).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the batch commits 0 onchain tx results for 1 onchain txs"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{ensure, Context, Result};
+use lib::batcher::{inputs::Inputs, tx::Tx};
+
+use crate::store::{BatchContext, BatchRecord, Store};
+
+/// Replays a proven batch on top of the `store` state, and commits it.
+///
+/// The txs are applied as the batcher program does: their IMTMutate is verified against the
+/// running root, the onchain txs with an invalid record proof are skipped, and the onchain txs are
+/// consumed from the onchain queue with their committed results. The offchain
+/// record proofs are not verified again: the batch proof, verified by the L1 contract, already
+/// attests to them.
+///
+/// Nothing is committed if the batch does not start from the store root and onchain queue
+/// position, or if it does not result in its committed new root, new tx hash, onchain queue end
+/// and onchain tx results.
+pub fn replay_batch(store: &mut Store, inputs: &Inputs) -> Result<BatchRecord> {
+    let result = apply_batch(store, inputs);
+    if result.is_err() {
+        store.rollback()?;
+    }
+
+    result?;
+    store.commit_batch(&BatchContext {
+        new_tx_hash: inputs.new_tx_hash,
+        timestamp: inputs.timestamp,
+        block_number: inputs.block_number,
+        block_hash: inputs.block_hash,
+        onchain_queue_hash: inputs.onchain_queue_end,
+    })
+}
+
+fn apply_batch(store: &mut Store, inputs: &Inputs) -> Result<()> {
+    ensure!(
+        inputs.old_root == store.root(),
+        "batch does not start from the store root"
+    );
+    ensure!(
+        inputs.onchain_queue_start == store.onchain_queue_hash()?,
+        "batch does not start from the store onchain queue position"
+    );
+
+    let mut root = inputs.old_root;
+    let mut tx_hash = [0; 32];
+    let mut onchain_results = Vec::with_capacity(inputs.onchain_results.len());
+
+    for (i, tx) in inputs.txs.iter().enumerate() {
+        tx_hash = tx.hash();
+
+        let new_root = tx
+            .verify_imt_mutate(&root)
+            .with_context(|| format!("failed to verify the IMTMutate of tx {i}"))?;
+
+        if let Tx::Onchain(onchain) = tx {
+            let result = inputs.onchain_tx_result(onchain);
+            onchain_results.push(result);
+            if !result.is_applied() {
+                continue;
+            }
+        }
+        root = new_root;
+
+        let (keyspace_id, new_key) = tx.keyspace_update();
+        store.set_key(keyspace_id, new_key, None)?;
+        ensure!(
+            store.root() == root,
+            "tx {i} does not match with the store state"
+        );
+    }
+
+    ensure!(
+        root == inputs.new_root,
+        "batch does not result in its new root"
+    );
+    ensure!(
+        tx_hash == inputs.new_tx_hash,
+        "batch does not result in its new tx hash"
+    );
+    ensure!(
+        inputs.consumed_onchain_queue() == inputs.onchain_queue_end,
+        "batch does not result in its onchain queue end"
+    );
+    ensure!(
+        onchain_results == inputs.onchain_results,
+        "batch does not result in its onchain tx results"
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use imt::circuits::{imt::Imt, mutate::IMTMutate};
+    use lib::{
+        batcher::{
+            proof::{plonk::PLONKProof, Proof},
+            tx::onchain::OnchainTxResult,
+        },
+        Hash,
+    };
+    use tiny_keccak::Keccak;
+
+    use super::*;
+
+    fn batch(old_root: Hash, new_root: Hash, txs: Vec<Tx>) -> Inputs {
+        let mut inputs = Inputs {
+            old_root,
+            new_root,
+            new_tx_hash: txs.last().unwrap().hash(),
+            timestamp: 0,
+            block_number: 0,
+            block_hash: [0; 32],
+            onchain_queue_start: [0; 32],
+            onchain_queue_end: [0; 32],
+            onchain_results: vec![],
+            txs,
+        };
+        inputs.onchain_queue_end = inputs.consumed_onchain_queue();
+        inputs.onchain_results = inputs.onchain_tx_results();
+        inputs
+    }
+
+    fn offchain(imt_mutate: IMTMutate<Hash, Hash>) -> Tx {
+        Tx::offchain(
+            imt_mutate,
+            [0; 32],
+            Proof::sp1([0; 32], [0; 32], None, None),
+        )
+    }
+
+    #[test]
+    fn test_replay_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::open(dir.path().join("keyspace.db")).unwrap();
+
+        let mut imt = Imt::new(Keccak::v256);
+        let root_0 = imt.root;
+        let txs = vec![
+            offchain(imt.insert_node([1; 32], [10; 32])),
+            offchain(imt.insert_node([2; 32], [20; 32])),
+        ];
+        let root_1 = imt.root;
+        replay_batch(&mut store, &batch(root_0, root_1, txs)).unwrap();
+
+        let txs = vec![offchain(imt.update_node([1; 32], [11; 32]))];
+        let root_2 = imt.root;
+        let inputs = batch(root_1, root_2, txs);
+        replay_batch(&mut store, &inputs).unwrap();
+
+        assert_eq!(store.root(), root_2);
+        assert_eq!(store.tx_hash().unwrap(), inputs.new_tx_hash);
+        assert_eq!(store.current_key(&[1; 32]).unwrap(), Some([11; 32]));
+        assert_eq!(store.batches_count().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_skip_onchain_tx() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::open(dir.path().join("keyspace.db")).unwrap();
+
+        let mut imt = Imt::new(Keccak::v256);
+        let old_root = imt.root;
+        let applied = offchain(imt.insert_node([1; 32], [10; 32]));
+        let new_root = imt.root;
+
+        // The onchain tx reads the Keyspace from a root that is not accepted by the batch.
+        let skipped = Tx::onchain(
+            imt.insert_node([2; 32], [20; 32]),
+            applied.hash(),
+            PLONKProof {
+                vk: vec![],
+                proof: vec![],
+                plonk_vk_hash: "0".to_string(),
+                zkvm_vk_hash: "0".to_string(),
+                storage_hash: [0; 32],
+                time_bounds: None,
+                keyspace_root: Some([0xff; 32]),
+            },
+        );
+
+        let inputs = batch(old_root, new_root, vec![applied, skipped]);
+        replay_batch(&mut store, &inputs).unwrap();
+
+        assert_eq!(store.root(), new_root);
+        assert_eq!(store.current_key(&[2; 32]).unwrap(), None);
+
+        // The skipped tx is consumed from the onchain queue.
+        assert_ne!(inputs.onchain_queue_end, [0; 32]);
+        assert_eq!(
+            store.onchain_queue_hash().unwrap(),
+            inputs.onchain_queue_end
+        );
+        assert_eq!(
+            inputs.onchain_results,
+            [OnchainTxResult::KeyspaceRootNotAccepted]
+        );
+    }
+
+    #[test]
+    fn test_invalid_batch_is_not_committed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::open(dir.path().join("keyspace.db")).unwrap();
+
+        let mut imt = Imt::new(Keccak::v256);
+        let old_root = imt.root;
+        let txs = vec![offchain(imt.insert_node([1; 32], [10; 32]))];
+
+        let err = replay_batch(&mut store, &batch(old_root, [0xff; 32], txs)).unwrap_err();
+        assert_eq!(err.to_string(), "batch does not result in its new root");
+        assert_eq!(store.root(), old_root);
+        assert_eq!(store.batches_count().unwrap(), 0);
+    }
+}
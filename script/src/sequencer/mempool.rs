@@ -0,0 +1,414 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{bail, Result};
+use lib::{batcher::proof::Proof, time_bounds::TimeBounds, Hash};
+
+/// A record proof accepted by the sequencer, waiting to be included in a batch.
+pub struct PendingTx<R> {
+    /// The submission id: keccak(record proof public values).
+    pub id: Hash,
+    pub keyspace_id: Hash,
+    /// The key the record proof was proven against.
+    pub current_key: Hash,
+    pub new_key: Hash,
+    /// The time bounds committed by the record proof, if any.
+    pub time_bounds: Option<TimeBounds>,
+    /// The Keyspace root committed by the record proof, if any.
+    pub keyspace_root: Option<Hash>,
+    /// The record program verifier key hash.
+    pub vk_hash: Hash,
+    pub storage_hash: Hash,
+    /// The proof to include in the offchain tx.
+    pub proof: Proof,
+    /// The record proof to verify out of band, for SP1 record proofs.
+    pub deferred: Option<R>,
+    /// The time (in seconds since the Unix epoch) the record proof was accepted at.
+    pub received_at: u64,
+}
+
+/// A pending tx removed from the mempool without being batched.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Dropped {
+    /// The submission id.
+    pub id: Hash,
+    pub reason: String,
+}
+
+/// The record proofs waiting to be batched.
+///
+/// Updates of the same Keyspace id are chained: an update is ready once its current key is the
+/// new key of the previous pending update of the id (or its committed key), so that any prefix of
+/// the ready txs applies in order. Updates whose current key is not reached yet wait as orphans
+/// for the update leading to it, and updates whose current key is already consumed by another
+/// pending update are stale: the first update accepted from a key wins.
+pub struct Mempool<R> {
+    /// The txs that apply in order, in the order they became ready.
+    ready: VecDeque<PendingTx<R>>,
+    /// The txs waiting for their current key to be reached, in arrival order.
+    orphans: Vec<PendingTx<R>>,
+}
+
+impl<R> Default for Mempool<R> {
+    fn default() -> Self {
+        Self {
+            ready: VecDeque::new(),
+            orphans: vec![],
+        }
+    }
+}
+
+impl<R> Mempool<R> {
+    /// Returns the number of ready txs.
+    pub fn len(&self) -> usize {
+        self.ready.len()
+    }
+
+    /// Returns whether there are no ready txs.
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_empty()
+    }
+
+    /// Returns whether the submission `id` is pending, ready or not.
+    pub fn contains(&self, id: &Hash) -> bool {
+        self.ready
+            .iter()
+            .chain(&self.orphans)
+            .any(|tx| tx.id == *id)
+    }
+
+    /// Returns the Keyspace ids with pending txs, ready or not.
+    pub fn keyspace_ids(&self) -> HashSet<Hash> {
+        self.ready
+            .iter()
+            .chain(&self.orphans)
+            .map(|tx| tx.keyspace_id)
+            .collect()
+    }
+
+    /// Returns the time the oldest ready tx was accepted at.
+    pub fn oldest_received_at(&self) -> Option<u64> {
+        self.ready.iter().map(|tx| tx.received_at).min()
+    }
+
+    /// Removes and returns (at most) the `count` first ready txs.
+    pub fn take(&mut self, count: usize) -> Vec<PendingTx<R>> {
+        let count = count.min(self.ready.len());
+        self.ready.drain(..count).collect()
+    }
+
+    /// Accepts `tx`, given the `committed_key` of its Keyspace id (the id itself if it is not
+    /// registered).
+    ///
+    /// Fails if the tx current key is stale, or if an orphan already applies from it. Returns the
+    /// orphans dropped as a result.
+    pub fn insert(&mut self, tx: PendingTx<R>, committed_key: Hash) -> Result<Vec<Dropped>> {
+        let tip = self.tip(&tx.keyspace_id, committed_key);
+
+        if tx.current_key != tip {
+            if self.is_consumed(&tx.keyspace_id, &tx.current_key) {
+                bail!("stale current key: a pending update already applies from it");
+            }
+            if self.orphans.iter().any(|orphan| {
+                orphan.keyspace_id == tx.keyspace_id && orphan.current_key == tx.current_key
+            }) {
+                bail!("conflicting update: another pending update applies from the same key");
+            }
+
+            self.orphans.push(tx);
+            return Ok(vec![]);
+        }
+
+        let keyspace_id = tx.keyspace_id;
+        self.ready.push_back(tx);
+        Ok(self.promote(&keyspace_id, committed_key))
+    }
+
+    /// Drops the txs that can no longer be batched, and chains the remaining ones again from the
+    /// committed keys (after a batch failed to be proven, the updates chained to its txs no
+    /// longer apply).
+    ///
+    /// The dropped txs are:
+    /// - the txs whose time bounds ended before `now`,
+    /// - the txs reading the Keyspace from another root than the `accepted_root`,
+    /// - the orphans received more than `max_orphan_age` seconds before `now`.
+    pub fn prune(
+        &mut self,
+        now: u64,
+        max_orphan_age: u64,
+        accepted_root: &Hash,
+        committed_keys: &HashMap<Hash, Hash>,
+    ) -> Vec<Dropped> {
+        let mut dropped = vec![];
+        let ready = drop_unbatchable(self.ready.drain(..), now, accepted_root, &mut dropped);
+        let orphans = drop_unbatchable(self.orphans.drain(..), now, accepted_root, &mut dropped);
+
+        // Chain the ready txs again from the committed keys, in the same order.
+        let committed_key = |keyspace_id: &Hash| {
+            committed_keys
+                .get(keyspace_id)
+                .copied()
+                .unwrap_or(*keyspace_id)
+        };
+        let mut tips = HashMap::new();
+        for tx in ready {
+            let tip = tips
+                .entry(tx.keyspace_id)
+                .or_insert_with(|| committed_key(&tx.keyspace_id));
+            if tx.current_key == *tip {
+                *tip = tx.new_key;
+                self.ready.push_back(tx);
+            } else {
+                self.orphans.push(tx);
+            }
+        }
+        self.orphans.extend(orphans);
+        self.orphans.sort_by_key(|tx| tx.received_at);
+
+        for keyspace_id in self.keyspace_ids() {
+            dropped.extend(self.promote(&keyspace_id, committed_key(&keyspace_id)));
+        }
+
+        let (expired, orphans) = self
+            .orphans
+            .drain(..)
+            .partition::<Vec<_>, _>(|tx| now.saturating_sub(tx.received_at) > max_orphan_age);
+        self.orphans = orphans;
+        dropped.extend(expired.into_iter().map(|tx| Dropped {
+            id: tx.id,
+            reason: "no pending update leads to its current key".to_string(),
+        }));
+
+        dropped
+    }
+
+    /// Returns the key the next update of `keyspace_id` must be proven against.
+    fn tip(&self, keyspace_id: &Hash, committed_key: Hash) -> Hash {
+        self.ready
+            .iter()
+            .rev()
+            .find(|tx| tx.keyspace_id == *keyspace_id)
+            .map_or(committed_key, |tx| tx.new_key)
+    }
+
+    /// Returns whether a ready tx of `keyspace_id` already applies from `key`.
+    fn is_consumed(&self, keyspace_id: &Hash, key: &Hash) -> bool {
+        self.ready
+            .iter()
+            .any(|tx| tx.keyspace_id == *keyspace_id && tx.current_key == *key)
+    }
+
+    /// Moves the orphans of `keyspace_id` that now apply to the ready txs, and drops the ones
+    /// that became stale.
+    fn promote(&mut self, keyspace_id: &Hash, committed_key: Hash) -> Vec<Dropped> {
+        loop {
+            let tip = self.tip(keyspace_id, committed_key);
+            let Some(index) = self
+                .orphans
+                .iter()
+                .position(|tx| tx.keyspace_id == *keyspace_id && tx.current_key == tip)
+            else {
+                break;
+            };
+
+            let tx = self.orphans.remove(index);
+            self.ready.push_back(tx);
+        }
+
+        let mut dropped = vec![];
+        let mut index = 0;
+        while index < self.orphans.len() {
+            let orphan = &self.orphans[index];
+            if orphan.keyspace_id == *keyspace_id
+                && self.is_consumed(keyspace_id, &orphan.current_key)
+            {
+                let orphan = self.orphans.remove(index);
+                dropped.push(Dropped {
+                    id: orphan.id,
+                    reason: "stale current key: a pending update already applies from it"
+                        .to_string(),
+                });
+            } else {
+                index += 1;
+            }
+        }
+
+        dropped
+    }
+}
+
+/// Filters out the txs whose time bounds ended before `now`, or reading the Keyspace from another
+/// root than the `accepted_root`.
+fn drop_unbatchable<R>(
+    txs: impl Iterator<Item = PendingTx<R>>,
+    now: u64,
+    accepted_root: &Hash,
+    dropped: &mut Vec<Dropped>,
+) -> Vec<PendingTx<R>> {
+    let mut batchable = vec![];
+    for tx in txs {
+        if tx
+            .time_bounds
+            .is_some_and(|time_bounds| time_bounds.valid_until < now)
+        {
+            dropped.push(Dropped {
+                id: tx.id,
+                reason: "record proof expired".to_string(),
+            });
+        } else if tx
+            .keyspace_root
+            .is_some_and(|keyspace_root| keyspace_root != *accepted_root)
+        {
+            dropped.push(Dropped {
+                id: tx.id,
+                reason: "record proof reads the Keyspace from a stale root".to_string(),
+            });
+        } else {
+            batchable.push(tx);
+        }
+    }
+
+    batchable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: Hash = [1; 32];
+
+    fn tx(id: u8, current_key: Hash, new_key: Hash, received_at: u64) -> PendingTx<()> {
+        PendingTx {
+            id: [id; 32],
+            keyspace_id: ID,
+            current_key,
+            new_key,
+            time_bounds: None,
+            keyspace_root: None,
+            vk_hash: [0; 32],
+            storage_hash: [0; 32],
+            proof: Proof::sp1([0; 32], [0; 32], None, None),
+            deferred: Some(()),
+            received_at,
+        }
+    }
+
+    fn ready_ids(mempool: &mut Mempool<()>) -> Vec<Hash> {
+        mempool
+            .take(usize::MAX)
+            .into_iter()
+            .map(|tx| tx.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_out_of_order_updates() {
+        let mut mempool = Mempool::default();
+
+        // The second update arrives before the one leading to its current key.
+        mempool
+            .insert(tx(2, [11; 32], [12; 32], 0), [10; 32])
+            .unwrap();
+        assert!(mempool.is_empty());
+        assert!(mempool.contains(&[2; 32]));
+
+        mempool
+            .insert(tx(1, [10; 32], [11; 32], 1), [10; 32])
+            .unwrap();
+        mempool
+            .insert(tx(3, [12; 32], [13; 32], 2), [10; 32])
+            .unwrap();
+        assert_eq!(mempool.len(), 3);
+        assert_eq!(ready_ids(&mut mempool), [[1; 32], [2; 32], [3; 32]]);
+    }
+
+    #[test]
+    fn test_reject_stale_updates() {
+        let mut mempool = Mempool::default();
+        mempool
+            .insert(tx(1, [10; 32], [11; 32], 0), [10; 32])
+            .unwrap();
+
+        // The committed key is already consumed by the first update.
+        let err = mempool
+            .insert(tx(2, [10; 32], [12; 32], 1), [10; 32])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stale current key: a pending update already applies from it"
+        );
+
+        mempool
+            .insert(tx(3, [20; 32], [21; 32], 2), [10; 32])
+            .unwrap();
+        let err = mempool
+            .insert(tx(4, [20; 32], [22; 32], 3), [10; 32])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "conflicting update: another pending update applies from the same key"
+        );
+        assert!(!mempool.contains(&[2; 32]));
+        assert!(!mempool.contains(&[4; 32]));
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut mempool = Mempool::default();
+        let root = [0xee; 32];
+        let committed_keys = HashMap::from([(ID, [10; 32])]);
+
+        let mut expired = tx(1, [10; 32], [11; 32], 0);
+        expired.time_bounds = Some(TimeBounds::until(5));
+        mempool.insert(expired, [10; 32]).unwrap();
+        let mut stale_root = tx(2, [11; 32], [12; 32], 0);
+        stale_root.keyspace_root = Some([0xdd; 32]);
+        mempool.insert(stale_root, [10; 32]).unwrap();
+        mempool
+            .insert(tx(3, [12; 32], [13; 32], 0), [10; 32])
+            .unwrap();
+        assert_eq!(mempool.len(), 3);
+
+        // The first update is dropped: the following ones no longer chain to the committed key.
+        let dropped = mempool.prune(10, 60, &root, &committed_keys);
+        assert_eq!(
+            dropped,
+            [
+                Dropped {
+                    id: [1; 32],
+                    reason: "record proof expired".to_string(),
+                },
+                Dropped {
+                    id: [2; 32],
+                    reason: "record proof reads the Keyspace from a stale root".to_string(),
+                },
+            ]
+        );
+        assert!(mempool.is_empty());
+        assert!(mempool.contains(&[3; 32]));
+
+        let dropped = mempool.prune(61, 60, &root, &committed_keys);
+        assert_eq!(
+            dropped,
+            [Dropped {
+                id: [3; 32],
+                reason: "no pending update leads to its current key".to_string(),
+            }]
+        );
+        assert!(!mempool.contains(&[3; 32]));
+    }
+
+    #[test]
+    fn test_prune_promotes_orphans() {
+        let mut mempool = Mempool::default();
+
+        // The update waits for a key being committed by another batch.
+        mempool
+            .insert(tx(1, [11; 32], [12; 32], 0), [10; 32])
+            .unwrap();
+        assert!(mempool.is_empty());
+
+        let committed_keys = HashMap::from([(ID, [11; 32])]);
+        assert_eq!(mempool.prune(1, 60, &[0; 32], &committed_keys), []);
+        assert_eq!(ready_ids(&mut mempool), [[1; 32]]);
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::{ensure, Result};
+use lib::{
+    batcher::{inputs::Inputs, proof::Proof, tx::Tx},
+    parse_record_public_values, Hash, RecordPublicValues,
+};
+use serde_json::Value;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::{
+    ingest::{onchain_txs, pending_requests, L1Block, UpdateRequest},
+    store::{BatchContext, Record, Store},
+};
+
+use mempool::{Dropped, Mempool, PendingTx};
+
+pub mod mempool;
+pub mod rpc;
+pub mod sp1;
+
+/// A record proof decoded from a `submitRecordProof` request.
+pub struct RecordSubmission<R> {
+    /// The record proof public values.
+    pub public_values: Vec<u8>,
+    /// The record program verifier key hash.
+    pub vk_hash: Hash,
+    pub storage_hash: Hash,
+    /// The proof to include in the offchain tx.
+    pub proof: Proof,
+    /// The record proof to verify out of band, for SP1 record proofs.
+    pub deferred: Option<R>,
+}
+
+/// Decodes the submitted record proofs and proves the batches cut by the sequencer.
+pub trait BatchProver: Send + Sync + 'static {
+    /// The record proofs the batcher program verifies out of band.
+    type RecordProof: Send + 'static;
+
+    /// Decodes the `submitRecordProof` request params.
+    fn decode_record_proof(&self, params: &Value) -> Result<RecordSubmission<Self::RecordProof>>;
+
+    /// Proves the batch, with its out of band record proofs in the order of their txs.
+    fn prove(
+        &self,
+        batch_number: u64,
+        inputs: Inputs,
+        record_proofs: Vec<Self::RecordProof>,
+    ) -> Result<()>;
+}
+
+/// When to cut a batch from the mempool, and the L1 state it is built on.
+pub struct SequencerConfig {
+    /// The maximum number of txs in a batch: a batch is cut as soon as it is reached.
+    pub max_batch_size: usize,
+    /// The maximum time (in seconds) a tx waits in the mempool before a batch is cut. Updates
+    /// whose current key is not reached after this time are dropped.
+    pub max_batch_age: u64,
+    /// Returns the L1 block the next batch is built on: it is called whenever a batch is cut.
+    pub l1_block: Box<dyn Fn() -> Result<L1Block> + Send + Sync>,
+    /// Returns the update requests emitted by the KeyStore contract since its deployment: the
+    /// ones not consumed yet are forced into the next batch.
+    pub update_requests: Box<dyn Fn() -> Result<Vec<UpdateRequest>> + Send + Sync>,
+}
+
+/// The status of a submitted record proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxStatus {
+    /// The record proof is in the mempool.
+    Pending,
+    /// The record proof is applied by a proven batch.
+    Included { batch: u64, tx_hash: Hash },
+    /// The batch including the record proof failed to be proven.
+    Failed { reason: String },
+    /// The record proof was dropped from the mempool without being batched.
+    Dropped { reason: String },
+}
+
+struct State<R> {
+    store: Store,
+    mempool: Mempool<R>,
+    statuses: HashMap<Hash, TxStatus>,
+}
+
+impl<R> State<R> {
+    fn drop_txs(&mut self, dropped: Vec<Dropped>) {
+        for Dropped { id, reason } in dropped {
+            self.statuses.insert(id, TxStatus::Dropped { reason });
+        }
+    }
+}
+
+/// A batch cut from the mempool, being proven.
+struct CutBatch<R> {
+    batch_number: u64,
+    context: BatchContext,
+    /// The submission id and tx hash of the batch txs.
+    txs: Vec<(Hash, Hash)>,
+    inputs: Inputs,
+    record_proofs: Vec<R>,
+}
+
+/// The sequencer: accepts record proofs into a mempool, and cuts and proves batches from it.
+pub struct Sequencer<P: BatchProver> {
+    prover: P,
+    config: SequencerConfig,
+    state: Mutex<State<P::RecordProof>>,
+    /// Held while a batch is cut and proven, so that batches are proven one at a time.
+    batching: Mutex<()>,
+}
+
+impl<P: BatchProver> Sequencer<P> {
+    pub fn new(store: Store, prover: P, config: SequencerConfig) -> Self {
+        Self {
+            prover,
+            config,
+            state: Mutex::new(State {
+                store,
+                mempool: Mempool::default(),
+                statuses: HashMap::new(),
+            }),
+            batching: Mutex::new(()),
+        }
+    }
+
+    /// Accepts a record proof into the mempool, and returns its submission id.
+    ///
+    /// The record proof must control its current key, be valid at `now`, and apply to the
+    /// Keyspace id once the pending updates it is chained to are applied (see [Mempool]).
+    pub fn submit(&self, params: &Value, now: u64) -> Result<Hash> {
+        let RecordSubmission {
+            public_values,
+            vk_hash,
+            storage_hash,
+            proof,
+            deferred,
+        } = self.prover.decode_record_proof(params)?;
+
+        let RecordPublicValues {
+            keyspace_id,
+            current_key,
+            new_key,
+            time_bounds,
+            keyspace_root,
+        } = parse_record_public_values(&public_values)?;
+        ensure!(
+            matches!(proof, Proof::SP1(_)) == deferred.is_some(),
+            "SP1 record proofs must be verified out of band"
+        );
+        ensure!(
+            proof.controls(&current_key),
+            "record proof does not control its current key"
+        );
+        if let Some(time_bounds) = time_bounds {
+            ensure!(
+                time_bounds.contains(now),
+                "record proof is not valid at the current time"
+            );
+        }
+
+        let id = submission_id(&public_values);
+
+        let mut state = self.state();
+        ensure!(
+            !state.mempool.contains(&id),
+            "record proof already submitted"
+        );
+        if let Some(keyspace_root) = keyspace_root {
+            ensure!(
+                keyspace_root == state.store.committed_root()?,
+                "record proof reads the Keyspace from a stale root"
+            );
+        }
+
+        // Chain the update to the batch being proven, if it updates the same id.
+        let committed_key = state.store.current_key(&keyspace_id)?;
+        let staged_key = state.store.staged_key(&keyspace_id)?;
+        ensure!(
+            staged_key == committed_key || Some(current_key) != committed_key,
+            "stale current key: a pending update already applies from it"
+        );
+
+        let dropped = state.mempool.insert(
+            PendingTx {
+                id,
+                keyspace_id,
+                current_key,
+                new_key,
+                time_bounds,
+                keyspace_root,
+                vk_hash,
+                storage_hash,
+                proof,
+                deferred,
+                received_at: now,
+            },
+            staged_key.unwrap_or(keyspace_id),
+        )?;
+        state.statuses.insert(id, TxStatus::Pending);
+        state.drop_txs(dropped);
+
+        Ok(id)
+    }
+
+    /// Returns the status of the record proof submitted with `id`.
+    pub fn tx_status(&self, id: &Hash) -> Option<TxStatus> {
+        self.state().statuses.get(id).cloned()
+    }
+
+    /// Returns the current key of `keyspace_id`, as of the latest proven batch.
+    pub fn current_key(&self, keyspace_id: &Hash) -> Result<Option<Hash>> {
+        self.state().store.current_key(keyspace_id)
+    }
+
+    /// Returns the Keyspace root, as of the latest proven batch.
+    pub fn root(&self) -> Result<Hash> {
+        self.state().store.committed_root()
+    }
+
+    /// Cuts a batch if the mempool is full enough or old enough, or if onchain txs are pending, and
+    /// proves it.
+    ///
+    /// Returns the number of the proven batch, if one was cut.
+    pub fn tick(&self, now: u64) -> Result<Option<u64>> {
+        let _batching = self.batching.lock().unwrap();
+
+        let Some(CutBatch {
+            batch_number,
+            context,
+            txs,
+            inputs,
+            record_proofs,
+        }) = self.cut_batch(now)?
+        else {
+            return Ok(None);
+        };
+
+        // Prove the batch without holding the state, so that the sequencer keeps serving requests.
+        let result = self.prover.prove(batch_number, inputs, record_proofs);
+
+        let mut state = self.state();
+        match result {
+            Ok(()) => {
+                state.store.commit_batch(&context)?;
+                for (id, tx_hash) in txs {
+                    state.statuses.insert(
+                        id,
+                        TxStatus::Included {
+                            batch: batch_number,
+                            tx_hash,
+                        },
+                    );
+                }
+
+                Ok(Some(batch_number))
+            }
+            Err(err) => {
+                state.store.rollback()?;
+                for (id, _) in txs {
+                    state.statuses.insert(
+                        id,
+                        TxStatus::Failed {
+                            reason: format!("{err:#}"),
+                        },
+                    );
+                }
+
+                Err(err.context(format!("failed to prove batch {batch_number}")))
+            }
+        }
+    }
+
+    /// Takes the txs of the next batch from the mempool, followed by the pending onchain txs, and
+    /// stages them in the store.
+    fn cut_batch(&self, now: u64) -> Result<Option<CutBatch<P::RecordProof>>> {
+        let requests = (self.config.update_requests)()?;
+        let mut state = self.state();
+        let onchain_queue_start = state.store.onchain_queue_hash()?;
+        let requests = pending_requests(requests, &onchain_queue_start)?;
+
+        let accepted_root = state.store.committed_root()?;
+        let committed_keys = state
+            .mempool
+            .keyspace_ids()
+            .into_iter()
+            .filter_map(|keyspace_id| {
+                let key = state.store.current_key(&keyspace_id).transpose()?;
+                Some(key.map(|key| (keyspace_id, key)))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        let dropped = state.mempool.prune(
+            now,
+            self.config.max_batch_age,
+            &accepted_root,
+            &committed_keys,
+        );
+        state.drop_txs(dropped);
+
+        // The pending onchain txs are included as soon as possible.
+        if state.mempool.is_empty() && requests.is_empty() {
+            return Ok(None);
+        }
+
+        let full = state.mempool.len() >= self.config.max_batch_size;
+        let old = state
+            .mempool
+            .oldest_received_at()
+            .is_some_and(|received_at| {
+                now.saturating_sub(received_at) >= self.config.max_batch_age
+            });
+        if !full && !old && requests.is_empty() {
+            return Ok(None);
+        }
+
+        let block = (self.config.l1_block)()?;
+        let batch_number = state.store.batches_count()?;
+        let old_root = state.store.root();
+        let mut tx_hash = state.store.tx_hash()?;
+
+        let pending = state.mempool.take(self.config.max_batch_size);
+        let mut txs = Vec::with_capacity(pending.len());
+        let mut tx_ids = Vec::with_capacity(pending.len());
+        let mut record_proofs = vec![];
+        for tx in pending {
+            let record = Record {
+                vk_hash: tx.vk_hash,
+                storage_hash: tx.storage_hash,
+                storage: None,
+            };
+            let imt_mutate = match state
+                .store
+                .set_key(tx.keyspace_id, tx.new_key, Some(record))
+            {
+                Ok(imt_mutate) => imt_mutate,
+                Err(err) => {
+                    state.store.rollback()?;
+                    return Err(err);
+                }
+            };
+
+            let offchain = Tx::offchain(imt_mutate, tx_hash, tx.proof);
+            tx_hash = offchain.hash();
+
+            txs.push(offchain);
+            tx_ids.push((tx.id, tx_hash));
+            record_proofs.extend(tx.deferred);
+        }
+
+        // Append the onchain txs, in queue order: the batches must consume the onchain queue.
+        match onchain_txs(&mut state.store, requests, tx_hash, now) {
+            Ok(onchain) => {
+                tx_hash = onchain.last().map_or(tx_hash, Tx::hash);
+                txs.extend(onchain);
+            }
+            Err(err) => {
+                state.store.rollback()?;
+                return Err(err);
+            }
+        }
+
+        let mut inputs = Inputs {
+            old_root,
+            new_root: state.store.root(),
+            new_tx_hash: tx_hash,
+            timestamp: now,
+            block_number: block.number,
+            block_hash: block.hash,
+            onchain_queue_start,
+            onchain_queue_end: onchain_queue_start,
+            onchain_results: vec![],
+
+            txs,
+        };
+        // The onchain txs are consumed from the onchain queue, in order, and their results committed.
+        inputs.onchain_queue_end = inputs.consumed_onchain_queue();
+        inputs.onchain_results = inputs.onchain_tx_results();
+
+        let context = BatchContext {
+            new_tx_hash: tx_hash,
+            timestamp: now,
+            block_number: block.number,
+            block_hash: block.hash,
+            onchain_queue_hash: inputs.onchain_queue_end,
+        };
+
+        Ok(Some(CutBatch {
+            batch_number,
+            context,
+            txs: tx_ids,
+            inputs,
+            record_proofs,
+        }))
+    }
+
+    fn state(&self) -> MutexGuard<'_, State<P::RecordProof>> {
+        self.state.lock().unwrap()
+    }
+}
+
+/// Returns the id of a submitted record proof: keccak(public values).
+pub fn submission_id(public_values: &[u8]) -> Hash {
+    let mut k = Keccak::v256();
+    k.update(public_values);
+
+    let mut id = [0; 32];
+    k.finalize(&mut id);
+    id
+}
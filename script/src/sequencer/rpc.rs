@@ -0,0 +1,560 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use axum::{extract::State, routing::post, Json, Router};
+use lib::Hash;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use super::{BatchProver, Sequencer, TxStatus};
+
+/// The JSON-RPC error code of invalid requests (unknown methods, invalid params).
+const INVALID_REQUEST: i64 = -32600;
+/// The JSON-RPC error code of requests that failed to be processed.
+const SERVER_ERROR: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Returns the JSON-RPC router of the sequencer, served on `POST /`.
+///
+/// Methods (hashes are 0x-prefixed hex strings):
+/// - `submitRecordProof`: accepts a record proof (see [BatchProver::decode_record_proof]) and
+///   returns `{ "txId" }`,
+/// - `getTxStatus { txId }`: returns `{ "status": "pending" }`,
+///   `{ "status": "included", "batch", "txHash" }`, `{ "status": "failed", "reason" }`,
+///   `{ "status": "dropped", "reason" }`, or null for unknown ids,
+/// - `getKey { keyspaceId }`: returns the current key of the id, or null if it is not registered,
+/// - `getRoot`: returns the latest proven Keyspace root.
+pub fn router<P: BatchProver>(sequencer: Arc<Sequencer<P>>) -> Router {
+    Router::new().route("/", post(handle)).with_state(sequencer)
+}
+
+/// Serves the sequencer JSON-RPC API on `listener`.
+pub async fn serve<P: BatchProver>(
+    listener: TcpListener,
+    sequencer: Arc<Sequencer<P>>,
+) -> Result<()> {
+    axum::serve(listener, router(sequencer)).await?;
+    Ok(())
+}
+
+async fn handle<P: BatchProver>(
+    State(sequencer): State<Arc<Sequencer<P>>>,
+    Json(request): Json<Request>,
+) -> Json<Value> {
+    // The methods block on the store, and `submitRecordProof` on the record proof verification:
+    // run them off the async runtime.
+    let id = request.id.clone();
+    let result =
+        tokio::task::spawn_blocking(move || dispatch(&sequencer, &request.method, &request.params))
+            .await
+            .unwrap_or_else(|err| Err((SERVER_ERROR, anyhow!(err))));
+
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, err)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": format!("{err:#}") },
+        }),
+    };
+
+    Json(response)
+}
+
+fn dispatch<P: BatchProver>(
+    sequencer: &Sequencer<P>,
+    method: &str,
+    params: &Value,
+) -> Result<Value, (i64, anyhow::Error)> {
+    let invalid = |err| (INVALID_REQUEST, err);
+    let failed = |err| (SERVER_ERROR, err);
+
+    match method {
+        "submitRecordProof" => {
+            let id = sequencer.submit(params, now()).map_err(failed)?;
+            Ok(json!({ "txId": to_hex(&id) }))
+        }
+        "getTxStatus" => {
+            let id = hash_param(params, "txId").map_err(invalid)?;
+            let status = sequencer.tx_status(&id).map(|status| match status {
+                TxStatus::Pending => json!({ "status": "pending" }),
+                TxStatus::Included { batch, tx_hash } => json!({
+                    "status": "included",
+                    "batch": batch,
+                    "txHash": to_hex(&tx_hash),
+                }),
+                TxStatus::Failed { reason } => json!({ "status": "failed", "reason": reason }),
+                TxStatus::Dropped { reason } => json!({ "status": "dropped", "reason": reason }),
+            });
+            Ok(status.unwrap_or(Value::Null))
+        }
+        "getKey" => {
+            let keyspace_id = hash_param(params, "keyspaceId").map_err(invalid)?;
+            let key = sequencer.current_key(&keyspace_id).map_err(failed)?;
+            Ok(key.map_or(Value::Null, |key| json!(to_hex(&key))))
+        }
+        "getRoot" => {
+            let root = sequencer.root().map_err(failed)?;
+            Ok(json!(to_hex(&root)))
+        }
+        _ => Err(invalid(anyhow!("unknown method: {method}"))),
+    }
+}
+
+fn hash_param(params: &Value, name: &str) -> Result<Hash> {
+    let param = params[name]
+        .as_str()
+        .with_context(|| format!("missing {name} param"))?;
+    from_hex(param).with_context(|| format!("invalid {name} param"))
+}
+
+pub fn to_hex(hash: &Hash) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+pub fn from_hex(hash: &str) -> Result<Hash> {
+    let hash = hex::decode(hash.trim_start_matches("0x"))?;
+    hash.try_into().map_err(|_| anyhow!("invalid hash length"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("invalid system time")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    };
+
+    use num_bigint::BigUint;
+
+    use lib::{
+        batcher::{inputs::Inputs, program::Program, proof::Proof},
+        keyspace_key_from_storage_hash, record_public_values, DELEGATION_APPROVAL,
+    };
+
+    use crate::{
+        ingest::{parse_update_requests, pending_requests, L1Block},
+        sequencer::{RecordSubmission, SequencerConfig},
+        store::Store,
+    };
+
+    use super::*;
+
+    const MOCK_VK_HASH: Hash = [0xaa; 32];
+
+    const LOGS: &str = include_str!("../../fixtures/update_requested_logs.json");
+
+    /// Returns the KeyStore contract emitting the fixture logs.
+    fn keystore() -> [u8; 20] {
+        hex::decode("5fbdb2315678afecb367f032d93f642f64180aa3")
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    /// Proves batches by running the batcher program natively.
+    #[derive(Default)]
+    struct MockProver {
+        /// Whether proving fails.
+        fail: AtomicBool,
+    }
+
+    impl BatchProver for MockProver {
+        type RecordProof = ();
+
+        /// Decodes `{ keyspaceId, storageHash, newKey[, keyspaceRoot][, plonkVkHash] }` params as
+        /// an SP1 record proof, or as a PLONK record proof if `plonkVkHash` is given, whose
+        /// current key is derived from the storage hash.
+        fn decode_record_proof(&self, params: &Value) -> Result<RecordSubmission<()>> {
+            let keyspace_id = hash_param(params, "keyspaceId")?;
+            let storage_hash = hash_param(params, "storageHash")?;
+            let new_key = hash_param(params, "newKey")?;
+            let keyspace_root = match params.get("keyspaceRoot") {
+                Some(_) => Some(hash_param(params, "keyspaceRoot")?),
+                None => None,
+            };
+            let current_key = keyspace_key_from_storage_hash(&MOCK_VK_HASH, &storage_hash);
+
+            let public_values = record_public_values(
+                &keyspace_id,
+                &current_key,
+                &new_key,
+                None,
+                keyspace_root.as_ref(),
+            );
+
+            let Some(plonk_vk_hash) = params.get("plonkVkHash") else {
+                return Ok(RecordSubmission {
+                    public_values,
+                    vk_hash: MOCK_VK_HASH,
+                    storage_hash,
+                    proof: Proof::sp1(MOCK_VK_HASH, storage_hash, None, keyspace_root),
+                    deferred: Some(()),
+                });
+            };
+
+            // As for SP1 record proofs, the PLONK record verifier key hash comes from the proof.
+            let proof = Proof::plonk(
+                &[],
+                &[],
+                plonk_vk_hash
+                    .as_str()
+                    .context("invalid plonkVkHash param")?
+                    .into(),
+                String::from("0"),
+                storage_hash,
+                None,
+                keyspace_root,
+            );
+            Ok(RecordSubmission {
+                public_values,
+                vk_hash: proof
+                    .record_vk_hash()
+                    .context("invalid plonkVkHash param")?,
+                storage_hash,
+                proof,
+                deferred: None,
+            })
+        }
+
+        fn prove(&self, _batch_number: u64, inputs: Inputs, _record_proofs: Vec<()>) -> Result<()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(anyhow!("prover failed"));
+            }
+
+            panic::catch_unwind(AssertUnwindSafe(|| {
+                Program::run(&inputs, |_vk_hash, _public_values_digest| {})
+            }))
+            .map_err(|_| anyhow!("batcher program failed"))
+        }
+    }
+
+    async fn call(url: &str, method: &str, params: Value) -> Value {
+        let response: Value = reqwest::Client::new()
+            .post(url)
+            .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        assert_eq!(response["id"], 1);
+        response
+    }
+
+    async fn start(
+        max_batch_size: usize,
+    ) -> (String, Arc<Sequencer<MockProver>>, tempfile::TempDir) {
+        start_with(SequencerConfig {
+            max_batch_size,
+            max_batch_age: 60,
+            l1_block: Box::new(|| Ok(L1Block::default())),
+            update_requests: Box::new(|| Ok(vec![])),
+        })
+        .await
+    }
+
+    async fn start_with(
+        config: SequencerConfig,
+    ) -> (String, Arc<Sequencer<MockProver>>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(dir.path().join("keyspace.db")).unwrap();
+        let sequencer = Arc::new(Sequencer::new(store, MockProver::default(), config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve(listener, sequencer.clone()));
+
+        (url, sequencer, dir)
+    }
+
+    /// Returns the id registered with the `storage_hash` storage.
+    fn keyspace_id(storage_hash: Hash) -> Hash {
+        keyspace_key_from_storage_hash(&MOCK_VK_HASH, &storage_hash)
+    }
+
+    fn register(storage_hash: Hash, new_key: Hash) -> Value {
+        json!({
+            "keyspaceId": to_hex(&keyspace_id(storage_hash)),
+            "storageHash": to_hex(&storage_hash),
+            "newKey": to_hex(&new_key),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_submit_and_batch() {
+        let (url, sequencer, _dir) = start(2).await;
+        let empty_root = call(&url, "getRoot", Value::Null).await["result"].clone();
+
+        let first = call(&url, "submitRecordProof", register([1; 32], [10; 32])).await;
+        let first_id = first["result"]["txId"].clone();
+        let status = call(&url, "getTxStatus", json!({ "txId": first_id })).await;
+        assert_eq!(status["result"]["status"], "pending");
+
+        // The batch is not full yet.
+        assert_eq!(sequencer.tick(now()).unwrap(), None);
+
+        call(&url, "submitRecordProof", register([2; 32], [20; 32])).await;
+        assert_eq!(sequencer.tick(now()).unwrap(), Some(0));
+
+        let status = call(&url, "getTxStatus", json!({ "txId": first_id })).await;
+        assert_eq!(status["result"]["status"], "included");
+        assert_eq!(status["result"]["batch"], 0);
+
+        let params = json!({ "keyspaceId": to_hex(&keyspace_id([1; 32])) });
+        let key = call(&url, "getKey", params).await;
+        assert_eq!(key["result"], to_hex(&[10; 32]));
+        let params = json!({ "keyspaceId": to_hex(&keyspace_id([3; 32])) });
+        let key = call(&url, "getKey", params).await;
+        assert_eq!(key["result"], Value::Null);
+
+        let root = call(&url, "getRoot", Value::Null).await;
+        assert_ne!(root["result"], empty_root);
+        assert_eq!(root["result"], to_hex(&sequencer.root().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_batch_cut_by_age() {
+        let (url, sequencer, _dir) = start(10).await;
+
+        call(&url, "submitRecordProof", register([1; 32], [10; 32])).await;
+
+        assert_eq!(sequencer.tick(now()).unwrap(), None);
+        assert_eq!(sequencer.tick(now() + 60).unwrap(), Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_submit_plonk() {
+        let (url, sequencer, _dir) = start(10).await;
+
+        // The decimal plonk verifier key hash decodes to the record verifier key hash.
+        let mut params = register([1; 32], [10; 32]);
+        params["plonkVkHash"] = BigUint::from_bytes_be(&MOCK_VK_HASH)
+            .to_str_radix(10)
+            .into();
+        let tx = call(&url, "submitRecordProof", params).await;
+        let status = call(&url, "getTxStatus", json!({ "txId": tx["result"]["txId"] })).await;
+        assert_eq!(status["result"]["status"], "pending");
+
+        let pending = &sequencer.state().mempool.take(1)[0];
+        assert!(matches!(pending.proof, Proof::PLONK(_)));
+        assert_eq!(pending.vk_hash, MOCK_VK_HASH);
+
+        // A PLONK proof of another record program does not control the current key.
+        let mut params = register([2; 32], [20; 32]);
+        params["plonkVkHash"] = BigUint::from_bytes_be(&[0xbb; 32]).to_str_radix(10).into();
+        let tx = call(&url, "submitRecordProof", params).await;
+        assert_eq!(
+            tx["error"]["message"],
+            "record proof does not control its current key"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_block() {
+        // The L1 block moves forward between the batches.
+        let block_number = AtomicU64::new(0);
+        let (url, sequencer, _dir) = start_with(SequencerConfig {
+            max_batch_size: 1,
+            max_batch_age: 60,
+            l1_block: Box::new(move || {
+                let number = block_number.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(L1Block {
+                    number,
+                    hash: [number as u8; 32],
+                })
+            }),
+            update_requests: Box::new(|| Ok(vec![])),
+        })
+        .await;
+
+        call(&url, "submitRecordProof", register([1; 32], [10; 32])).await;
+        assert_eq!(sequencer.tick(now()).unwrap(), Some(0));
+        call(&url, "submitRecordProof", register([2; 32], [20; 32])).await;
+        assert_eq!(sequencer.tick(now()).unwrap(), Some(1));
+
+        let state = sequencer.state();
+        for batch_number in 0..2 {
+            let batch = state.store.batch(batch_number).unwrap().unwrap();
+            assert_eq!(batch.block_number, batch_number + 1);
+            assert_eq!(batch.block_hash, [batch_number as u8 + 1; 32]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_onchain_txs_inclusion() {
+        let (url, sequencer, _dir) = start_with(SequencerConfig {
+            max_batch_size: 10,
+            max_batch_age: 60,
+            l1_block: Box::new(|| Ok(L1Block::default())),
+            update_requests: Box::new(|| parse_update_requests(LOGS, &keystore())),
+        })
+        .await;
+
+        // The pending onchain txs are batched right away, after the offchain txs.
+        call(&url, "submitRecordProof", register([1; 32], [10; 32])).await;
+        assert_eq!(sequencer.tick(now()).unwrap(), Some(0));
+
+        {
+            let state = sequencer.state();
+            let batch = state.store.batch(0).unwrap().unwrap();
+            let requests = parse_update_requests(LOGS, &keystore()).unwrap();
+            assert!(pending_requests(requests, &batch.onchain_queue_hash)
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                state.store.current_key(&keyspace_id([1; 32])).unwrap(),
+                Some([10; 32])
+            );
+        }
+
+        // The consumed onchain txs are not batched again.
+        assert_eq!(sequencer.tick(now() + 60).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_chained_updates() {
+        let (url, sequencer, _dir) = start(10).await;
+        let id = keyspace_id([1; 32]);
+
+        call(
+            &url,
+            "submitRecordProof",
+            register([1; 32], keyspace_id([2; 32])),
+        )
+        .await;
+
+        // The update is proven against the key set by the pending registration.
+        let update = |storage_hash: Hash, new_key: Hash| {
+            json!({
+                "keyspaceId": to_hex(&id),
+                "storageHash": to_hex(&storage_hash),
+                "newKey": to_hex(&new_key),
+            })
+        };
+        let tx = call(
+            &url,
+            "submitRecordProof",
+            update([2; 32], keyspace_id([3; 32])),
+        )
+        .await;
+        assert!(tx["result"]["txId"].is_string());
+
+        // Another update from the same key is stale.
+        let tx = call(
+            &url,
+            "submitRecordProof",
+            update([2; 32], keyspace_id([4; 32])),
+        )
+        .await;
+        assert_eq!(
+            tx["error"]["message"],
+            "stale current key: a pending update already applies from it"
+        );
+
+        // The registration is not proven against the id itself: it waits for an update leading
+        // to its current key.
+        let params = json!({
+            "keyspaceId": to_hex(&[9; 32]),
+            "storageHash": to_hex(&[1; 32]),
+            "newKey": to_hex(&[10; 32]),
+        });
+        let tx = call(&url, "submitRecordProof", params).await;
+        assert!(tx["result"]["txId"].is_string());
+
+        assert_eq!(sequencer.tick(now() + 60).unwrap(), Some(0));
+        assert_eq!(
+            sequencer.current_key(&id).unwrap(),
+            Some(keyspace_id([3; 32]))
+        );
+        assert_eq!(sequencer.current_key(&[9; 32]).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch() {
+        let (url, sequencer, _dir) = start(1).await;
+        let root = sequencer.root().unwrap();
+        let id = keyspace_id([1; 32]);
+
+        let tx = call(
+            &url,
+            "submitRecordProof",
+            register([1; 32], keyspace_id([2; 32])),
+        )
+        .await;
+        let tx_id = tx["result"]["txId"].clone();
+        let params = json!({
+            "keyspaceId": to_hex(&id),
+            "storageHash": to_hex(&[2; 32]),
+            "newKey": to_hex(&[20; 32]),
+        });
+        let chained = call(&url, "submitRecordProof", params).await;
+        let chained_id = chained["result"]["txId"].clone();
+
+        sequencer.prover.fail.store(true, Ordering::SeqCst);
+        assert!(sequencer.tick(now()).is_err());
+
+        let status = call(&url, "getTxStatus", json!({ "txId": tx_id })).await;
+        assert_eq!(status["result"]["status"], "failed");
+        assert_eq!(sequencer.root().unwrap(), root);
+        assert_eq!(sequencer.current_key(&id).unwrap(), None);
+
+        // The chained update no longer applies, and is eventually dropped.
+        sequencer.prover.fail.store(false, Ordering::SeqCst);
+        assert_eq!(sequencer.tick(now()).unwrap(), None);
+        assert_eq!(sequencer.tick(now() + 61).unwrap(), None);
+
+        let status = call(&url, "getTxStatus", json!({ "txId": chained_id })).await;
+        assert_eq!(status["result"]["status"], "dropped");
+        assert_eq!(
+            status["result"]["reason"],
+            "no pending update leads to its current key"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_requests() {
+        let (url, _sequencer, _dir) = start(1).await;
+
+        let response = call(&url, "getBalance", Value::Null).await;
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let response = call(&url, "getKey", json!({ "keyspaceId": "0x01" })).await;
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let mut params = register([1; 32], [10; 32]);
+        params["keyspaceRoot"] = to_hex(&DELEGATION_APPROVAL).into();
+        let response = call(&url, "submitRecordProof", params).await;
+        assert_eq!(
+            response["error"]["message"],
+            "record public values are a delegation approval, not an update"
+        );
+
+        let params = register([1; 32], [10; 32]);
+        call(&url, "submitRecordProof", params.clone()).await;
+        let response = call(&url, "submitRecordProof", params).await;
+        assert_eq!(
+            response["error"]["message"],
+            "record proof already submitted"
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use lib::{
+    batcher::{inputs::Inputs, proof::Proof},
+    parse_record_public_values, RecordPublicValues,
+};
+use serde_json::Value;
+use sp1_sdk::{
+    HashableKey, ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1ProvingKey, SP1VerifyingKey,
+};
+
+use crate::{parse_record_proof, witness::BatchWitness};
+
+use super::{BatchProver, RecordSubmission};
+
+/// Proves the sequencer batches with the batcher program.
+pub struct Sp1BatchProver {
+    client: ProverClient,
+    batcher_pk: SP1ProvingKey,
+    /// The directory the batch witnesses are saved to (see `reprove`).
+    witness_dir: PathBuf,
+}
+
+impl Sp1BatchProver {
+    pub fn new(client: ProverClient, batcher_elf: &[u8], witness_dir: PathBuf) -> Self {
+        let (batcher_pk, _) = client.setup(batcher_elf);
+        Self {
+            client,
+            batcher_pk,
+            witness_dir,
+        }
+    }
+}
+
+impl BatchProver for Sp1BatchProver {
+    type RecordProof = (SP1ProofWithPublicValues, SP1VerifyingKey);
+
+    /// Decodes `{ recordProof }` params, where `recordProof` is a record proof as saved by
+    /// [crate::save_record_proof_to_file]: an SP1 compressed or PLONK record proof, with its
+    /// storage hash and verifying key.
+    ///
+    /// The record proof is verified natively, so that invalid proofs never reach a batch.
+    fn decode_record_proof(&self, params: &Value) -> Result<RecordSubmission<Self::RecordProof>> {
+        let record_proof = params
+            .get("recordProof")
+            .context("missing recordProof param")?;
+        let (storage_hash, _storage, record_vk, record_proof, plonk_proof) =
+            parse_record_proof(&record_proof.to_string()).context("invalid recordProof param")?;
+        self.client
+            .verify(&record_proof, &record_vk)
+            .context("invalid record proof")?;
+
+        let public_values = record_proof.public_values.as_slice().to_vec();
+        let RecordPublicValues {
+            keyspace_id,
+            current_key,
+            new_key,
+            time_bounds,
+            keyspace_root,
+        } = parse_record_public_values(&public_values)?;
+
+        let proof = match &record_proof.proof {
+            SP1Proof::Compressed(_) => Proof::sp1(
+                record_vk.hash_bytes(),
+                storage_hash,
+                time_bounds,
+                keyspace_root,
+            ),
+            SP1Proof::Plonk(_) => {
+                let plonk_proof = plonk_proof.context("missing PLONK proof")?;
+                Proof::plonk(
+                    &plonk_proof.vk,
+                    &plonk_proof.proof,
+                    plonk_proof.plonk_vk_hash,
+                    plonk_proof.zkvm_vk_hash,
+                    storage_hash,
+                    time_bounds,
+                    keyspace_root,
+                )
+            }
+            _ => bail!("record proof should be compressed to be recursively verified"),
+        };
+
+        // The PLONK proof placed in the tx is verified by the batcher, not the SP1 proof it wraps:
+        // verify it against the public values, which with the controls check on submission binds
+        // it to the updated record.
+        if let Proof::PLONK(plonk_proof) = &proof {
+            ensure!(
+                plonk_proof.verify_update(&keyspace_id, &current_key, &new_key),
+                "invalid PLONK proof"
+            );
+        }
+        let vk_hash = proof
+            .record_vk_hash()
+            .context("invalid PLONK proof verifier key hash")?;
+
+        let deferred = match record_proof.proof {
+            SP1Proof::Compressed(_) => Some((record_proof, record_vk)),
+            _ => None,
+        };
+
+        Ok(RecordSubmission {
+            public_values,
+            vk_hash,
+            storage_hash,
+            proof,
+            deferred,
+        })
+    }
+
+    fn prove(
+        &self,
+        batch_number: u64,
+        inputs: Inputs,
+        record_proofs: Vec<Self::RecordProof>,
+    ) -> Result<()> {
+        let witness = BatchWitness::new(inputs, record_proofs);
+        fs::create_dir_all(&self.witness_dir)?;
+        witness.save(
+            &self
+                .witness_dir
+                .join(format!("batch_{batch_number}.witness"))
+                .to_string_lossy(),
+        );
+
+        let batch_proof = self
+            .client
+            .prove(&self.batcher_pk, witness.stdin())
+            .plonk()
+            .run()?;
+        ensure!(
+            batch_proof.public_values.as_slice() == witness.public_values.as_slice(),
+            "batch proof public values do not match with the witness"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, ensure, Context, Result};
+use imt::circuits::imt::Imt;
+use lib::Hash;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::store::Store;
+
+/// The magic bytes starting every snapshot.
+pub const MAGIC: &[u8; 4] = b"KSPS";
+/// The current snapshot format version.
+pub const VERSION: u8 = 1;
+
+/// A snapshot of the Keyspace state, to archive it or move it between machines.
+///
+/// The binary format (integers are big-endian) is:
+/// ```text
+/// magic (4) || version (1) || root (32) || tx_hash (32) || onchain_queue_hash (32)
+///     || batch_number (8)
+///     || batcher_vk_hash (32) || record_vk_hashes count (4) || record_vk_hashes (32 each)
+///     || entries count (8) || entries (keyspace_id (32) || key (32) each)
+///     || checksum (32)
+/// ```
+/// where the checksum is the keccak256 of all the preceding bytes.
+///
+/// The entries are listed in insertion order, so that inserting them into an empty IMT rebuilds
+/// the Keyspace IMT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The Keyspace root.
+    pub root: Hash,
+    /// The latest tx hash.
+    pub tx_hash: Hash,
+    /// The onchain queue hash consumed up to by the batches.
+    pub onchain_queue_hash: Hash,
+    /// The number of batches the state results from.
+    pub batch_number: u64,
+    /// The batcher program verifier key hash.
+    pub batcher_vk_hash: Hash,
+    /// The verifier key hashes of the record programs known to the state.
+    pub record_vk_hashes: Vec<Hash>,
+    /// The (Keyspace id, current key) pairs, in insertion order.
+    pub entries: Vec<(Hash, Hash)>,
+}
+
+/// The Keyspace ids that changed between two snapshots.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// The ids registered since the older snapshot, in insertion order.
+    pub inserted: Vec<Hash>,
+    /// The ids whose key changed since the older snapshot, in insertion order.
+    pub updated: Vec<Hash>,
+}
+
+impl Snapshot {
+    /// Takes a snapshot of the committed state of `store`.
+    pub fn from_store(store: &Store, batcher_vk_hash: Hash) -> Result<Self> {
+        let snapshot = Self {
+            root: store.committed_root()?,
+            tx_hash: store.tx_hash()?,
+            onchain_queue_hash: store.onchain_queue_hash()?,
+            batch_number: store.batches_count()?,
+            batcher_vk_hash,
+            record_vk_hashes: store.record_vk_hashes()?,
+            entries: store.entries()?,
+        };
+        snapshot.imt()?;
+
+        Ok(snapshot)
+    }
+
+    /// Encodes the snapshot.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            4 + 1
+                + 32
+                + 32
+                + 32
+                + 8
+                + 32
+                + 4
+                + 32 * self.record_vk_hashes.len()
+                + 8
+                + 64 * self.entries.len()
+                + 32,
+        );
+
+        bytes.extend(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend(self.root);
+        bytes.extend(self.tx_hash);
+        bytes.extend(self.onchain_queue_hash);
+        bytes.extend(self.batch_number.to_be_bytes());
+        bytes.extend(self.batcher_vk_hash);
+
+        bytes.extend((self.record_vk_hashes.len() as u32).to_be_bytes());
+        for vk_hash in &self.record_vk_hashes {
+            bytes.extend(vk_hash);
+        }
+
+        bytes.extend((self.entries.len() as u64).to_be_bytes());
+        for (keyspace_id, key) in &self.entries {
+            bytes.extend(keyspace_id);
+            bytes.extend(key);
+        }
+
+        let checksum = checksum(&bytes);
+        bytes.extend(checksum);
+        bytes
+    }
+
+    /// Decodes a snapshot, checking its checksum and that its entries rebuild its root.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() >= 32, "snapshot is too short");
+        let (body, expected_checksum) = bytes.split_at(bytes.len() - 32);
+        ensure!(
+            checksum(body) == expected_checksum,
+            "invalid snapshot checksum"
+        );
+
+        let mut reader = Reader(body);
+        ensure!(reader.take(4)? == MAGIC, "not a Keyspace snapshot");
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            bail!("unsupported snapshot version: {version}");
+        }
+
+        let root = reader.hash()?;
+        let tx_hash = reader.hash()?;
+        let onchain_queue_hash = reader.hash()?;
+        let batch_number = reader.u64()?;
+        let batcher_vk_hash = reader.hash()?;
+
+        let record_vk_hashes_count = u32::from_be_bytes(reader.take(4)?.try_into()?);
+        let record_vk_hashes = (0..record_vk_hashes_count)
+            .map(|_| reader.hash())
+            .collect::<Result<Vec<_>>>()?;
+
+        let entries_count = reader.u64()?;
+        ensure!(
+            entries_count.checked_mul(64) == Some(reader.0.len() as u64),
+            "invalid snapshot entries length"
+        );
+        let entries = (0..entries_count)
+            .map(|_| Ok((reader.hash()?, reader.hash()?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let snapshot = Self {
+            root,
+            tx_hash,
+            onchain_queue_hash,
+            batch_number,
+            batcher_vk_hash,
+            record_vk_hashes,
+            entries,
+        };
+        snapshot.imt()?;
+
+        Ok(snapshot)
+    }
+
+    /// Rebuilds the Keyspace IMT, and ensures it matches with the snapshot root.
+    pub fn imt(&self) -> Result<Imt<Hash, Hash, Keccak>> {
+        let mut ids = HashSet::new();
+        ensure!(
+            self.entries
+                .iter()
+                .all(|(keyspace_id, _)| ids.insert(keyspace_id)),
+            "snapshot contains a duplicated Keyspace id"
+        );
+
+        let imt = rebuild(&self.entries);
+        ensure!(
+            imt.root == self.root,
+            "snapshot entries do not match with the snapshot root"
+        );
+
+        Ok(imt)
+    }
+
+    /// Lists the Keyspace ids inserted and updated from `self` to the `newer` snapshot.
+    ///
+    /// Fails if `self` is not an ancestor of `newer`: Keyspace ids are never removed, and are
+    /// always inserted after the existing ones.
+    pub fn diff(&self, newer: &Snapshot) -> Result<SnapshotDiff> {
+        ensure!(
+            self.entries.len() <= newer.entries.len(),
+            "the newer snapshot has less entries"
+        );
+
+        let mut diff = SnapshotDiff::default();
+        for ((keyspace_id, key), (newer_keyspace_id, newer_key)) in
+            self.entries.iter().zip(&newer.entries)
+        {
+            ensure!(
+                keyspace_id == newer_keyspace_id,
+                "the snapshots have diverging entries"
+            );
+            if key != newer_key {
+                diff.updated.push(*keyspace_id);
+            }
+        }
+
+        diff.inserted = newer.entries[self.entries.len()..]
+            .iter()
+            .map(|(keyspace_id, _)| *keyspace_id)
+            .collect();
+
+        Ok(diff)
+    }
+}
+
+/// Rebuilds the IMT by inserting `entries` in order.
+pub(crate) fn rebuild(entries: &[(Hash, Hash)]) -> Imt<Hash, Hash, Keccak> {
+    let mut imt = Imt::new(Keccak::v256);
+    for (keyspace_id, key) in entries {
+        imt.insert_node(*keyspace_id, *key);
+    }
+
+    imt
+}
+
+fn checksum(bytes: &[u8]) -> Hash {
+    let mut k = Keccak::v256();
+    let mut checksum = [0; 32];
+    k.update(bytes);
+    k.finalize(&mut checksum);
+    checksum
+}
+
+/// Reads the fixed-size fields of a snapshot.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "snapshot is too short");
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn hash(&mut self) -> Result<Hash> {
+        self.take(32)?.try_into().context("invalid hash")
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::BatchContext;
+
+    use super::*;
+
+    fn snapshot(entries: Vec<(Hash, Hash)>) -> Snapshot {
+        Snapshot {
+            root: rebuild(&entries).root,
+            tx_hash: [0xf1; 32],
+            onchain_queue_hash: [0xc1; 32],
+            batch_number: 3,
+            batcher_vk_hash: [0xb0; 32],
+            record_vk_hashes: vec![[0xa1; 32], [0xa2; 32]],
+            entries,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let snapshot = snapshot(vec![([1; 32], [10; 32]), ([2; 32], [20; 32])]);
+        let bytes = snapshot.to_bytes();
+
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_invalid_checksum() {
+        let mut bytes = snapshot(vec![([1; 32], [10; 32])]).to_bytes();
+        bytes[10] ^= 1;
+
+        let err = Snapshot::from_bytes(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "invalid snapshot checksum");
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let mut bytes = snapshot(vec![]).to_bytes();
+        bytes.truncate(bytes.len() - 32);
+        bytes[4] = VERSION + 1;
+        let checksum = checksum(&bytes);
+        bytes.extend(checksum);
+
+        let err = Snapshot::from_bytes(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "unsupported snapshot version: 2");
+    }
+
+    #[test]
+    fn test_root_mismatch() {
+        let mut snapshot = snapshot(vec![([1; 32], [10; 32])]);
+        snapshot.root = [0xff; 32];
+
+        let err = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "snapshot entries do not match with the snapshot root"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot(vec![([1; 32], [10; 32]), ([2; 32], [20; 32])]);
+        let new = snapshot(vec![
+            ([1; 32], [10; 32]),
+            ([2; 32], [21; 32]),
+            ([3; 32], [30; 32]),
+        ]);
+
+        assert_eq!(
+            old.diff(&new).unwrap(),
+            SnapshotDiff {
+                inserted: vec![[3; 32]],
+                updated: vec![[2; 32]],
+            }
+        );
+        assert!(new.diff(&old).is_err());
+
+        let diverging = snapshot(vec![([2; 32], [20; 32]), ([1; 32], [10; 32])]);
+        assert!(old.diff(&diverging).is_err());
+    }
+
+    #[test]
+    fn test_export_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut store = Store::open(dir.path().join("source.db")).unwrap();
+        store.set_key([1; 32], [10; 32], None).unwrap();
+        store.set_key([2; 32], [20; 32], None).unwrap();
+        store
+            .commit_batch(&BatchContext {
+                new_tx_hash: [0xf1; 32],
+                timestamp: 1_000,
+                block_number: 42,
+                block_hash: [0xbb; 32],
+                onchain_queue_hash: [0xc1; 32],
+            })
+            .unwrap();
+        store.set_key([1; 32], [11; 32], None).unwrap();
+        store
+            .commit_batch(&BatchContext {
+                new_tx_hash: [0xf2; 32],
+                timestamp: 1_001,
+                block_number: 43,
+                block_hash: [0xbc; 32],
+                onchain_queue_hash: [0xc2; 32],
+            })
+            .unwrap();
+
+        // Staged mutations are not exported.
+        store.set_key([3; 32], [30; 32], None).unwrap();
+
+        let snapshot = Snapshot::from_store(&store, [0xb0; 32]).unwrap();
+        assert_eq!(
+            snapshot.entries,
+            vec![([1; 32], [11; 32]), ([2; 32], [20; 32])]
+        );
+        assert_eq!(snapshot.batch_number, 2);
+
+        let bytes = snapshot.to_bytes();
+        let restored = Store::restore(
+            dir.path().join("restored.db"),
+            &Snapshot::from_bytes(&bytes).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(restored.root(), snapshot.root);
+        assert_eq!(restored.tx_hash().unwrap(), [0xf2; 32]);
+        assert_eq!(restored.onchain_queue_hash().unwrap(), [0xc2; 32]);
+        assert_eq!(restored.batches_count().unwrap(), 2);
+        assert_eq!(restored.current_key(&[1; 32]).unwrap(), Some([11; 32]));
+        assert_eq!(
+            Snapshot::from_store(&restored, [0xb0; 32])
+                .unwrap()
+                .to_bytes(),
+            bytes
+        );
+
+        // A store can only be restored once.
+        drop(restored);
+        let Err(err) = Store::restore(dir.path().join("restored.db"), &snapshot) else {
+            panic!("restored a non empty store");
+        };
+        assert_eq!(err.to_string(), "the store is not empty");
+    }
+}
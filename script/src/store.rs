@@ -0,0 +1,579 @@
+use std::{collections::BTreeSet, path::Path};
+
+use anyhow::{bail, ensure, Context, Result};
+use imt::circuits::{imt::Imt, mutate::IMTMutate};
+use lib::{keyspace_proof::KeyspaceProof, Hash};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
+use tiny_keccak::Keccak;
+
+use crate::snapshot::{rebuild, Snapshot};
+
+/// The IMT nodes: the insertion position and current key of each Keyspace id.
+const NODES: TableDefinition<[u8; 32], (u64, [u8; 32])> = TableDefinition::new("nodes");
+/// The record behind the current key of each Keyspace id, when known.
+const RECORDS: TableDefinition<[u8; 32], &[u8]> = TableDefinition::new("records");
+/// The committed batches, indexed by their position.
+const BATCHES: TableDefinition<u64, &[u8]> = TableDefinition::new("batches");
+/// The latest Keyspace root, tx hash and consumed onchain queue hash.
+const META: TableDefinition<&str, [u8; 32]> = TableDefinition::new("meta");
+/// The number of committed batches, including the ones preceding a restored snapshot.
+const COUNTERS: TableDefinition<&str, u64> = TableDefinition::new("counters");
+
+const ROOT: &str = "root";
+const TX_HASH: &str = "tx_hash";
+const ONCHAIN_QUEUE_HASH: &str = "onchain_queue_hash";
+const BATCHES_COUNT: &str = "batches";
+
+/// A mutation of the Keyspace IMT: the insertion of a new id, or the update of an existing one.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Mutation {
+    Insert { keyspace_id: Hash, key: Hash },
+    Update { keyspace_id: Hash, key: Hash },
+}
+
+/// The record controlling a Keyspace id: the preimage of its current key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Record {
+    /// The record program verifier key hash.
+    pub vk_hash: Hash,
+    /// The record storage hash.
+    pub storage_hash: Hash,
+    /// The record storage, if known.
+    pub storage: Option<Vec<u8>>,
+}
+
+/// The public inputs of a committed batch, and the mutations it applied.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BatchRecord {
+    pub old_root: Hash,
+    pub new_root: Hash,
+    pub new_tx_hash: Hash,
+    pub timestamp: u64,
+    pub block_number: u64,
+    pub block_hash: Hash,
+    /// The onchain queue hash consumed up to by the batch.
+    pub onchain_queue_hash: Hash,
+    pub mutations: Vec<Mutation>,
+}
+
+/// The L1 context, tx hash and consumed onchain queue hash of a batch being committed.
+pub struct BatchContext {
+    pub new_tx_hash: Hash,
+    pub timestamp: u64,
+    pub block_number: u64,
+    pub block_hash: Hash,
+    pub onchain_queue_hash: Hash,
+}
+
+/// The persistent Keyspace state of the host, backed by an embedded database.
+///
+/// The IMT nodes are persisted, and the IMT is kept in memory. As the IMT can only be built by
+/// inserting nodes, it is rebuilt on open by inserting the persisted nodes in their original order,
+/// which reproduces the committed root. Mutations are staged with [Store::set_key] and persisted
+/// atomically, with the batch, by [Store::commit_batch].
+pub struct Store {
+    db: Database,
+    imt: Imt<Hash, Hash, Keccak>,
+    staged: Vec<(Mutation, Option<Record>)>,
+}
+
+impl Store {
+    /// Opens (or creates) the store at `path` and rebuilds the Keyspace IMT.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = Database::create(path).context("failed to open the store")?;
+
+        // Create the tables, so that they can be opened by read transactions.
+        let txn = db.begin_write()?;
+        txn.open_table(NODES)?;
+        txn.open_table(RECORDS)?;
+        txn.open_table(BATCHES)?;
+        txn.open_table(META)?;
+        txn.open_table(COUNTERS)?;
+        txn.commit()?;
+
+        let mut store = Self {
+            db,
+            imt: Imt::new(Keccak::v256),
+            staged: vec![],
+        };
+        store.imt = store.load()?;
+
+        Ok(store)
+    }
+
+    /// Returns the Keyspace root, including the staged mutations.
+    pub fn root(&self) -> Hash {
+        self.imt.root
+    }
+
+    /// Returns the Keyspace IMT, including the staged mutations.
+    pub fn imt(&self) -> &Imt<Hash, Hash, Keccak> {
+        &self.imt
+    }
+
+    /// Returns the committed Keyspace root, excluding the staged mutations.
+    pub fn committed_root(&self) -> Result<Hash> {
+        let txn = self.db.begin_read()?;
+        let meta = txn.open_table(META)?;
+        let root = meta.get(ROOT)?.map(|root| root.value());
+
+        Ok(root.unwrap_or(Imt::new(Keccak::v256).root))
+    }
+
+    /// Returns the latest committed tx hash.
+    pub fn tx_hash(&self) -> Result<Hash> {
+        let txn = self.db.begin_read()?;
+        let meta = txn.open_table(META)?;
+        let tx_hash = meta.get(TX_HASH)?.map(|tx_hash| tx_hash.value());
+
+        Ok(tx_hash.unwrap_or_default())
+    }
+
+    /// Returns the onchain queue hash consumed up to by the committed batches.
+    pub fn onchain_queue_hash(&self) -> Result<Hash> {
+        let txn = self.db.begin_read()?;
+        let meta = txn.open_table(META)?;
+        let queue_hash = meta
+            .get(ONCHAIN_QUEUE_HASH)?
+            .map(|queue_hash| queue_hash.value());
+
+        Ok(queue_hash.unwrap_or_default())
+    }
+
+    /// Returns the committed current key of `keyspace_id`, if it is registered.
+    pub fn current_key(&self, keyspace_id: &Hash) -> Result<Option<Hash>> {
+        let txn = self.db.begin_read()?;
+        let nodes = txn.open_table(NODES)?;
+        let key = nodes.get(keyspace_id)?.map(|node| node.value().1);
+
+        Ok(key)
+    }
+
+    /// Returns the current key of `keyspace_id` including the staged mutations, if it is
+    /// registered.
+    pub fn staged_key(&self, keyspace_id: &Hash) -> Result<Option<Hash>> {
+        let staged = self
+            .staged
+            .iter()
+            .rev()
+            .find_map(|(mutation, _)| match mutation {
+                Mutation::Insert {
+                    keyspace_id: id,
+                    key,
+                }
+                | Mutation::Update {
+                    keyspace_id: id,
+                    key,
+                } => (id == keyspace_id).then_some(*key),
+            });
+
+        match staged {
+            Some(key) => Ok(Some(key)),
+            None => self.current_key(keyspace_id),
+        }
+    }
+
+    /// Returns the committed record of `keyspace_id`, if it is known.
+    pub fn record(&self, keyspace_id: &Hash) -> Result<Option<Record>> {
+        let txn = self.db.begin_read()?;
+        let records = txn.open_table(RECORDS)?;
+        let record = records
+            .get(keyspace_id)?
+            .map(|record| bincode::deserialize(record.value()))
+            .transpose()?;
+
+        Ok(record)
+    }
+
+    /// Returns the number of committed batches.
+    pub fn batches_count(&self) -> Result<u64> {
+        let txn = self.db.begin_read()?;
+        let counters = txn.open_table(COUNTERS)?;
+        let count = counters.get(BATCHES_COUNT)?.map(|count| count.value());
+
+        Ok(count.unwrap_or_default())
+    }
+
+    /// Returns the committed batch at `index`.
+    ///
+    /// The batches preceding a restored snapshot (see [Store::restore]) are not available.
+    pub fn batch(&self, index: u64) -> Result<Option<BatchRecord>> {
+        let txn = self.db.begin_read()?;
+        let batches = txn.open_table(BATCHES)?;
+        let batch = batches
+            .get(index)?
+            .map(|batch| bincode::deserialize(batch.value()))
+            .transpose()?;
+
+        Ok(batch)
+    }
+
+    /// Stages the update of `keyspace_id` to `key`, inserting it if it is not registered yet.
+    ///
+    /// Returns the IMTMutate to include in the batch tx.
+    pub fn set_key(
+        &mut self,
+        keyspace_id: Hash,
+        key: Hash,
+        record: Option<Record>,
+    ) -> Result<IMTMutate<Hash, Hash>> {
+        let registered = self.current_key(&keyspace_id)?.is_some()
+            || self.staged.iter().any(|(mutation, _)| match mutation {
+                Mutation::Insert {
+                    keyspace_id: id, ..
+                } => *id == keyspace_id,
+                Mutation::Update { .. } => false,
+            });
+
+        let (mutation, imt_mutate) = match registered {
+            true => (
+                Mutation::Update { keyspace_id, key },
+                self.imt.update_node(keyspace_id, key),
+            ),
+            false => (
+                Mutation::Insert { keyspace_id, key },
+                self.imt.insert_node(keyspace_id, key),
+            ),
+        };
+        self.staged.push((mutation, record));
+
+        Ok(imt_mutate)
+    }
+
+    /// Returns the IMTMutate that [Store::set_key] would stage to set `keyspace_id` to `key`,
+    /// without staging it.
+    pub fn probe_key(&self, keyspace_id: Hash, key: Hash) -> Result<IMTMutate<Hash, Hash>> {
+        // Generate the mutation on a copy of the in-memory IMT, which includes the staged mutations.
+        let mut imt = self.imt.clone();
+        Ok(match self.staged_key(&keyspace_id)? {
+            Some(_) => imt.update_node(keyspace_id, key),
+            None => imt.insert_node(keyspace_id, key),
+        })
+    }
+
+    /// Atomically persists the staged mutations and the batch built from them.
+    pub fn commit_batch(&mut self, context: &BatchContext) -> Result<BatchRecord> {
+        let txn = self.db.begin_write()?;
+        let batch = {
+            let mut nodes = txn.open_table(NODES)?;
+            let mut records = txn.open_table(RECORDS)?;
+            let mut batches = txn.open_table(BATCHES)?;
+            let mut meta = txn.open_table(META)?;
+            let mut counters = txn.open_table(COUNTERS)?;
+
+            let old_root = meta
+                .get(ROOT)?
+                .map(|root| root.value())
+                .unwrap_or(Imt::new(Keccak::v256).root);
+
+            for (mutation, record) in &self.staged {
+                let (keyspace_id, key, position) = match mutation {
+                    Mutation::Insert { keyspace_id, key } => (keyspace_id, key, nodes.len()?),
+                    Mutation::Update { keyspace_id, key } => {
+                        let position = nodes
+                            .get(keyspace_id)?
+                            .context("update of an unknown Keyspace id")?
+                            .value()
+                            .0;
+                        (keyspace_id, key, position)
+                    }
+                };
+
+                nodes.insert(keyspace_id, (position, *key))?;
+                match record {
+                    Some(record) => {
+                        records.insert(keyspace_id, bincode::serialize(record)?.as_slice())?
+                    }
+                    None => records.remove(keyspace_id)?,
+                };
+            }
+
+            let batch = BatchRecord {
+                old_root,
+                new_root: self.imt.root,
+                new_tx_hash: context.new_tx_hash,
+                timestamp: context.timestamp,
+                block_number: context.block_number,
+                block_hash: context.block_hash,
+                onchain_queue_hash: context.onchain_queue_hash,
+                mutations: self
+                    .staged
+                    .iter()
+                    .map(|(mutation, _)| mutation.clone())
+                    .collect(),
+            };
+            let index = counters
+                .get(BATCHES_COUNT)?
+                .map(|count| count.value())
+                .unwrap_or_default();
+            batches.insert(index, bincode::serialize(&batch)?.as_slice())?;
+            counters.insert(BATCHES_COUNT, index + 1)?;
+
+            meta.insert(ROOT, self.imt.root)?;
+            meta.insert(TX_HASH, context.new_tx_hash)?;
+            meta.insert(ONCHAIN_QUEUE_HASH, context.onchain_queue_hash)?;
+
+            batch
+        };
+        txn.commit()?;
+
+        self.staged.clear();
+        Ok(batch)
+    }
+
+    /// Returns the committed (Keyspace id, current key) pairs, in insertion order.
+    ///
+    /// Inserting them in this order into an empty IMT rebuilds the committed Keyspace IMT.
+    pub fn entries(&self) -> Result<Vec<(Hash, Hash)>> {
+        let txn = self.db.begin_read()?;
+        let nodes = txn.open_table(NODES)?;
+
+        let mut entries = vec![];
+        for entry in nodes.iter()? {
+            let (keyspace_id, node) = entry?;
+            let (position, key) = node.value();
+            entries.push((position, keyspace_id.value(), key));
+        }
+        entries.sort_unstable_by_key(|(position, ..)| *position);
+
+        Ok(entries
+            .into_iter()
+            .map(|(_, keyspace_id, key)| (keyspace_id, key))
+            .collect())
+    }
+
+    /// Returns the distinct verifier key hashes of the known records, sorted.
+    pub fn record_vk_hashes(&self) -> Result<Vec<Hash>> {
+        let txn = self.db.begin_read()?;
+        let records = txn.open_table(RECORDS)?;
+
+        let mut vk_hashes = BTreeSet::new();
+        for entry in records.iter()? {
+            let (_, record) = entry?;
+            let record: Record = bincode::deserialize(record.value())?;
+            vk_hashes.insert(record.vk_hash);
+        }
+
+        Ok(vk_hashes.into_iter().collect())
+    }
+
+    /// Proves the committed key of `keyspace_id`, or that it is not registered.
+    ///
+    /// The proofs are IMT mutations: they are generated on a copy of the committed IMT, rebuilt from
+    /// the nodes, as the in-memory IMT includes the staged mutations.
+    pub fn prove(&self, keyspace_id: &Hash) -> Result<KeyspaceProof> {
+        let entries = self.entries()?;
+        let mut imt = rebuild(&entries);
+        let root = imt.root;
+
+        let key = entries
+            .iter()
+            .find_map(|(id, key)| (id == keyspace_id).then_some(*key));
+        let imt_mutate = match key {
+            Some(key) => imt.update_node(*keyspace_id, key),
+            None => imt.insert_node(*keyspace_id, Hash::default()),
+        };
+
+        Ok(KeyspaceProof {
+            root,
+            keyspace_id: *keyspace_id,
+            imt_mutate,
+        })
+    }
+
+    /// Creates the store at `path` from `snapshot`, which must be a new store.
+    ///
+    /// The records and the batches history are not part of the snapshot: the restored store only
+    /// knows the current keys, the root, the tx hash, the onchain queue hash and the number of
+    /// batches.
+    pub fn restore(path: impl AsRef<Path>, snapshot: &Snapshot) -> Result<Self> {
+        let mut store = Self::open(path)?;
+        ensure!(
+            store.batches_count()? == 0 && store.entries()?.is_empty(),
+            "the store is not empty"
+        );
+
+        for (keyspace_id, key) in &snapshot.entries {
+            store.set_key(*keyspace_id, *key, None)?;
+        }
+        ensure!(
+            store.root() == snapshot.root,
+            "snapshot entries do not match with the snapshot root"
+        );
+
+        let txn = store.db.begin_write()?;
+        {
+            let mut nodes = txn.open_table(NODES)?;
+            let mut meta = txn.open_table(META)?;
+            let mut counters = txn.open_table(COUNTERS)?;
+
+            for (position, (mutation, _)) in (0..).zip(&store.staged) {
+                let Mutation::Insert { keyspace_id, key } = mutation else {
+                    bail!("snapshot contains a duplicated Keyspace id");
+                };
+                nodes.insert(keyspace_id, (position, *key))?;
+            }
+
+            meta.insert(ROOT, snapshot.root)?;
+            meta.insert(TX_HASH, snapshot.tx_hash)?;
+            meta.insert(ONCHAIN_QUEUE_HASH, snapshot.onchain_queue_hash)?;
+            counters.insert(BATCHES_COUNT, snapshot.batch_number)?;
+        }
+        txn.commit()?;
+
+        store.staged.clear();
+        Ok(store)
+    }
+
+    /// Discards the staged mutations.
+    pub fn rollback(&mut self) -> Result<()> {
+        self.staged.clear();
+        self.imt = self.load()?;
+        Ok(())
+    }
+
+    /// Rebuilds the Keyspace IMT from the committed nodes, and ensures it matches with the
+    /// committed root.
+    fn load(&self) -> Result<Imt<Hash, Hash, Keccak>> {
+        let imt = rebuild(&self.entries()?);
+
+        if let Some(root) = self.db.begin_read()?.open_table(META)?.get(ROOT)? {
+            ensure!(
+                imt.root == root.value(),
+                "rebuilt root does not match with the committed root"
+            );
+        }
+
+        Ok(imt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(new_tx_hash: Hash) -> BatchContext {
+        BatchContext {
+            new_tx_hash,
+            timestamp: 1_000,
+            block_number: 42,
+            block_hash: [0xbb; 32],
+            onchain_queue_hash: [0xcc; 32],
+        }
+    }
+
+    fn record(storage: &[u8]) -> Record {
+        Record {
+            vk_hash: [0xaa; 32],
+            storage_hash: lib::hash_storage(storage),
+            storage: Some(storage.to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_commit_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keyspace.db");
+
+        let root = {
+            let mut store = Store::open(&path).unwrap();
+            let empty_root = store.root();
+
+            store
+                .set_key([1; 32], [10; 32], Some(record(b"one")))
+                .unwrap();
+            store.set_key([2; 32], [20; 32], None).unwrap();
+            store.commit_batch(&context([0xf1; 32])).unwrap();
+
+            store.set_key([1; 32], [11; 32], None).unwrap();
+            store.set_key([3; 32], [30; 32], None).unwrap();
+            let batch = store.commit_batch(&context([0xf2; 32])).unwrap();
+            assert_eq!(
+                batch.mutations,
+                vec![
+                    Mutation::Update {
+                        keyspace_id: [1; 32],
+                        key: [11; 32]
+                    },
+                    Mutation::Insert {
+                        keyspace_id: [3; 32],
+                        key: [30; 32]
+                    },
+                ]
+            );
+            assert_ne!(batch.old_root, empty_root);
+
+            store.root()
+        };
+
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.root(), root);
+        assert_eq!(
+            store.entries().unwrap(),
+            vec![
+                ([1; 32], [11; 32]),
+                ([2; 32], [20; 32]),
+                ([3; 32], [30; 32])
+            ]
+        );
+        assert_eq!(store.tx_hash().unwrap(), [0xf2; 32]);
+        assert_eq!(store.onchain_queue_hash().unwrap(), [0xcc; 32]);
+        assert_eq!(
+            store
+                .prove(&[1; 32])
+                .unwrap()
+                .verify_inclusion(&root, &[1; 32])
+                .unwrap(),
+            [11; 32]
+        );
+        store
+            .prove(&[4; 32])
+            .unwrap()
+            .verify_exclusion(&root, &[4; 32])
+            .unwrap();
+        assert_eq!(store.current_key(&[1; 32]).unwrap(), Some([11; 32]));
+        assert_eq!(store.current_key(&[4; 32]).unwrap(), None);
+        assert_eq!(store.record(&[1; 32]).unwrap(), None);
+        assert_eq!(store.batches_count().unwrap(), 2);
+
+        let first = store.batch(0).unwrap().unwrap();
+        let second = store.batch(1).unwrap().unwrap();
+        assert_eq!(first.new_root, second.old_root);
+        assert_eq!(second.new_root, root);
+    }
+
+    #[test]
+    fn test_staged_mutations_are_not_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keyspace.db");
+
+        let root = {
+            let mut store = Store::open(&path).unwrap();
+            store
+                .set_key([1; 32], [10; 32], Some(record(b"one")))
+                .unwrap();
+            store.commit_batch(&context([0xf1; 32])).unwrap();
+            let root = store.root();
+
+            let probed = store.probe_key([1; 32], [11; 32]).unwrap();
+            assert_eq!(store.root(), root);
+
+            store.set_key([1; 32], [11; 32], None).unwrap();
+            assert_ne!(store.root(), root);
+            assert_eq!(probed.verify(Keccak::v256, root).unwrap(), store.root());
+            assert_eq!(store.staged_key(&[1; 32]).unwrap(), Some([11; 32]));
+            assert_eq!(store.current_key(&[1; 32]).unwrap(), Some([10; 32]));
+            root
+        };
+
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.root(), root);
+        assert_eq!(store.record(&[1; 32]).unwrap(), Some(record(b"one")));
+
+        store.set_key([2; 32], [20; 32], None).unwrap();
+        store.rollback().unwrap();
+        assert_eq!(store.root(), root);
+        assert_eq!(store.batches_count().unwrap(), 1);
+    }
+}
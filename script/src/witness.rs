@@ -0,0 +1,121 @@
+use std::fs;
+
+use lib::batcher::inputs::Inputs;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{SP1Proof, SP1ProofWithPublicValues, SP1Stdin, SP1VerifyingKey};
+
+/// The full witness of a batch, to prove it again exactly as it was first proven.
+#[derive(Serialize, Deserialize)]
+pub struct BatchWitness {
+    /// The batcher program inputs.
+    pub inputs: Inputs,
+    /// The SP1 record proofs verified out of band, with their verifying keys, in the order of the
+    /// offchain txs that carry them.
+    pub record_proofs: Vec<(SP1ProofWithPublicValues, SP1VerifyingKey)>,
+    /// The public values the batch proof is expected to commit.
+    pub public_values: Vec<u8>,
+}
+
+impl BatchWitness {
+    pub fn new(
+        inputs: Inputs,
+        record_proofs: Vec<(SP1ProofWithPublicValues, SP1VerifyingKey)>,
+    ) -> Self {
+        let public_values = inputs.public_values();
+        Self {
+            inputs,
+            record_proofs,
+            public_values,
+        }
+    }
+
+    /// Builds the batcher program stdin: the deferred record proofs, then the inputs.
+    pub fn stdin(&self) -> SP1Stdin {
+        let mut stdin = SP1Stdin::new();
+        for (record_proof, record_vk) in &self.record_proofs {
+            let SP1Proof::Compressed(proof) = &record_proof.proof else {
+                panic!("record proof should be compressed to be recursively verified");
+            };
+            stdin.write_proof(proof.clone(), record_vk.vk.clone());
+        }
+        stdin.write(&self.inputs);
+        stdin
+    }
+
+    /// Ensures the batch proof commits the expected public values.
+    pub fn check_public_values(&self, batch_proof: &SP1ProofWithPublicValues) {
+        assert_eq!(
+            batch_proof.public_values.as_slice(),
+            self.public_values.as_slice(),
+            "batch proof public values do not match with the witness"
+        );
+    }
+
+    pub fn save(&self, file: &str) {
+        let witness = bincode::serialize(self).expect("failed to serialize batch witness");
+        fs::write(file, witness).expect("failed to save batch witness");
+    }
+
+    pub fn load(file: &str) -> Self {
+        let witness = fs::read(file).expect("failed to read batch witness");
+        bincode::deserialize(&witness).expect("failed to deserialize batch witness")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use imt::circuits::imt::Imt;
+    use lib::batcher::{proof::Proof, tx::Tx};
+    use tiny_keccak::Keccak;
+
+    use super::*;
+
+    fn inputs() -> Inputs {
+        let mut imt = Imt::new(Keccak::v256);
+        let old_root = imt.root;
+        let tx = Tx::offchain(
+            imt.insert_node([1; 32], [10; 32]),
+            [0; 32],
+            Proof::sp1([0xaa; 32], [0xbb; 32], None, None),
+        );
+
+        Inputs {
+            old_root,
+            new_root: imt.root,
+            new_tx_hash: tx.hash(),
+            timestamp: 1_000,
+            block_number: 42,
+            block_hash: [0xcc; 32],
+            onchain_queue_start: [0; 32],
+            onchain_queue_end: [0; 32],
+            onchain_results: vec![],
+            txs: vec![tx],
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("batch_0.witness");
+        let file = file.to_str().unwrap();
+
+        let witness = BatchWitness::new(inputs(), vec![]);
+        witness.save(file);
+
+        let loaded = BatchWitness::load(file);
+        assert_eq!(
+            bincode::serialize(&loaded.inputs).unwrap(),
+            bincode::serialize(&witness.inputs).unwrap()
+        );
+        assert!(loaded.record_proofs.is_empty());
+        assert_eq!(loaded.public_values, witness.inputs.public_values());
+
+        // The stdin replays the inputs exactly as they were first written.
+        let mut stdin = loaded.stdin();
+        assert!(stdin.proofs.is_empty());
+        assert_eq!(
+            bincode::serialize(&stdin.read::<Inputs>()).unwrap(),
+            bincode::serialize(&witness.inputs).unwrap()
+        );
+    }
+}